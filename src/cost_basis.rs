@@ -0,0 +1,170 @@
+use crate::dtos::CostBasis;
+use crate::error::{Error, Result};
+use crate::exchange::types::{get_operation_type, OperationType, Trade};
+use rust_decimal::prelude::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Weighted average entry price per pair, net of the sells done after each buy. A trade
+/// with an amount that is not a number leaves its pair with the parse error
+pub fn calc_cost_basis(
+    trades: Vec<Trade>,
+    include_fees: bool,
+) -> HashMap<String, Result<CostBasis>> {
+    let mut sorted_trades = trades;
+    sorted_trades.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+
+    sorted_trades.into_iter().fold(
+        HashMap::new(),
+        |mut acc: HashMap<String, Result<CostBasis>>, trade| {
+            let amounts = parse_amount(&trade, "price", &trade.price).and_then(|price| {
+                Ok((
+                    price,
+                    parse_amount(&trade, "volume", &trade.volume)?,
+                    parse_amount(&trade, "fee", &trade.fee)?,
+                ))
+            });
+
+            let entry = acc
+                .entry(trade.pair.clone())
+                .or_insert_with(|| Ok(CostBasis::from(trade.pair.clone())));
+
+            let (price, vol, fee) = match amounts {
+                Ok(amounts) => amounts,
+                Err(err) => {
+                    if entry.is_ok() {
+                        *entry = Err(err);
+                    }
+
+                    return acc;
+                }
+            };
+
+            let cost_basis = match entry {
+                Ok(cost_basis) => cost_basis,
+                Err(_) => return acc,
+            };
+
+            if trade.trade_type == get_operation_type(OperationType::BUY) {
+                cost_basis.cost += price * vol;
+                cost_basis.fees += fee;
+                cost_basis.quantity += vol;
+                cost_basis.operation_time = trade.time as i64;
+            } else if trade.trade_type == get_operation_type(OperationType::SELL)
+                && !cost_basis.quantity.is_zero()
+            {
+                let sold = vol.min(cost_basis.quantity);
+                let remaining = (cost_basis.quantity - sold) / cost_basis.quantity;
+
+                cost_basis.cost *= remaining;
+                cost_basis.fees *= remaining;
+                cost_basis.quantity -= sold;
+            }
+
+            cost_basis.average_price = cost_basis.calc_average_price(include_fees);
+
+            acc
+        },
+    )
+}
+
+fn parse_amount(trade: &Trade, name: &str, amount: &str) -> Result<Decimal> {
+    Decimal::from_str(amount).map_err(|_| {
+        Error::Parse(format!(
+            "{} of the {} trade at {} is not a number: {}",
+            name, trade.pair, trade.time, amount
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::calc_cost_basis;
+    use crate::exchange::types::Trade;
+    use rust_decimal::Decimal;
+
+    fn trade(pair: &str, trade_type: &str, price: &str, vol: &str, fee: &str, time: f64) -> Trade {
+        Trade {
+            pair: pair.to_string(),
+            trade_type: trade_type.to_string(),
+            price: price.to_string(),
+            volume: vol.to_string(),
+            fee: fee.to_string(),
+            time,
+        }
+    }
+
+    #[test]
+    fn should_calc_weighted_average_price() {
+        let trades = vec![
+            trade("KAVAEUR", "buy", "5.0", "50", "1.0", 160300.0),
+            trade("KAVAEUR", "buy", "2.0", "100", "1.0", 160000.0),
+            trade("KAVAEUR", "sell", "3.5", "50", "0.5", 160200.0),
+            trade("KAVAEUR", "buy", "4.0", "100", "1.0", 160100.0),
+            trade("OXTEUR", "buy", "0.29", "4000", "2.0", 160000.0),
+        ];
+
+        let cost_basis = calc_cost_basis(trades, false);
+        let kava = cost_basis.get("KAVAEUR").unwrap().as_ref().unwrap();
+        let oxt = cost_basis.get("OXTEUR").unwrap().as_ref().unwrap();
+
+        assert_eq!(kava.average_price, Decimal::new(35, 1));
+        assert_eq!(kava.quantity, Decimal::new(200, 0));
+        assert_eq!(kava.operation_time, 160300);
+        assert_eq!(oxt.average_price, Decimal::new(29, 2));
+    }
+
+    #[test]
+    fn should_include_fees_in_average_price() {
+        let trades = vec![
+            trade("KAVAEUR", "buy", "2.0", "100", "1.0", 160000.0),
+            trade("KAVAEUR", "buy", "4.0", "100", "1.0", 160100.0),
+        ];
+
+        let cost_basis = calc_cost_basis(trades, true);
+        let kava = cost_basis.get("KAVAEUR").unwrap().as_ref().unwrap();
+
+        assert_eq!(kava.average_price, Decimal::new(301, 2));
+        assert_eq!(kava.calc_break_even_price(), Decimal::new(302, 2));
+    }
+
+    #[test]
+    fn should_reset_cost_basis_when_position_is_closed() {
+        let trades = vec![
+            trade("KAVAEUR", "buy", "2.0", "100", "0.0", 160000.0),
+            trade("KAVAEUR", "sell", "3.0", "100", "0.0", 160100.0),
+            trade("KAVAEUR", "buy", "4.0", "10", "0.0", 160200.0),
+        ];
+
+        let cost_basis = calc_cost_basis(trades, false);
+        let kava = cost_basis.get("KAVAEUR").unwrap().as_ref().unwrap();
+
+        assert_eq!(kava.average_price, Decimal::new(4, 0));
+        assert_eq!(kava.quantity, Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn should_report_trade_amounts_that_are_not_numbers() {
+        let trades = vec![
+            trade("KAVAEUR", "buy", "2.0", "100", "1.0", 160000.0),
+            trade("KAVAEUR", "buy", "4.0", "1,5", "1.0", 160100.0),
+            trade("KAVAEUR", "buy", "3.0", "100", "1.0", 160200.0),
+            trade("OXTEUR", "buy", "0.29", "4000", "", 160000.0),
+            trade("CRVEUR", "buy", "5.0", "3000", "2.0", 160000.0),
+        ];
+
+        let cost_basis = calc_cost_basis(trades, false);
+
+        assert!(cost_basis.get("KAVAEUR").unwrap().is_err());
+        assert!(cost_basis.get("OXTEUR").unwrap().is_err());
+        assert_eq!(
+            cost_basis
+                .get("CRVEUR")
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .average_price,
+            Decimal::new(5, 0)
+        );
+    }
+}
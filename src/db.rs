@@ -1,42 +1,479 @@
+use crate::dtos::StopFill;
+use crate::error::{Error, Result};
+use crate::exchange::types::{AssetPair, OrderType, PriceSource, Trade};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sled::{Db, Error, IVec, Result};
+use sled::Db;
+use std::collections::HashMap;
 use std::str;
+use std::str::FromStr;
 
+/// Candle intervals in minutes of the Kraken OHLC data
+const OHLC_INTERVALS: [u32; 9] = [1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+
+#[derive(Clone)]
 pub struct DancespieleDB {
     db: Db,
+    dry_run: bool,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
 pub struct Percentage {
     pub pair: String,
     pub new_stop_loss: String,
     pub next_stop_loss: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_loss_offset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_offset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take_profit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take_profit_volume: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take_profit_order_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_interval: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atr_multiplier: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atr_period: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub break_even: Option<String>,
+}
+
+impl Percentage {
+    /// Optional number of the pair configuration, invalid values are reported instead of
+    /// falling back to the default so the pair does not place a different order than configured
+    fn parse_decimal(&self, name: &str, value: &Option<String>) -> Result<Option<Decimal>> {
+        value
+            .as_ref()
+            .map(|value| {
+                Decimal::from_str(value).map_err(|_| {
+                    Error::Config(format!(
+                        "{} of {} must be a number, got {}",
+                        name, self.pair, value
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    fn invalid_value(&self, name: &str, value: &str) -> Error {
+        Error::Config(format!("{} of {} is not valid: {}", name, self.pair, value))
+    }
+
+    pub fn get_stop_loss_offset(&self, default_offset: Decimal) -> Result<Decimal> {
+        Ok(self
+            .parse_decimal("stop_loss_offset", &self.stop_loss_offset)?
+            .unwrap_or(default_offset))
+    }
+
+    pub fn get_order_type(&self) -> Result<OrderType> {
+        match self.order_type.as_deref() {
+            None | Some("stop-loss") => Ok(OrderType::StopLoss),
+            Some("stop-loss-limit") => Ok(OrderType::StopLossLimit),
+            Some(order_type) => Err(self.invalid_value("order_type", order_type)),
+        }
+    }
+
+    pub fn get_take_profit(&self) -> Result<Option<Decimal>> {
+        self.parse_decimal("take_profit", &self.take_profit)
+    }
+
+    /// Percentage of the position sold by the take profit, the stop loss protects the rest
+    pub fn get_take_profit_volume(&self) -> Result<Decimal> {
+        Ok(self
+            .parse_decimal("take_profit_volume", &self.take_profit_volume)?
+            .unwrap_or_else(|| Decimal::new(50, 0)))
+    }
+
+    pub fn get_take_profit_order_type(&self) -> Result<OrderType> {
+        match self.take_profit_order_type.as_deref() {
+            None | Some("take-profit") => Ok(OrderType::TakeProfit),
+            Some("take-profit-limit") => Ok(OrderType::TakeProfitLimit),
+            Some(order_type) => Err(self.invalid_value("take_profit_order_type", order_type)),
+        }
+    }
+
+    pub fn get_limit_offset(&self, default_offset: Decimal) -> Result<Decimal> {
+        Ok(self
+            .parse_decimal("limit_offset", &self.limit_offset)?
+            .unwrap_or(default_offset))
+    }
+
+    /// Candle interval in minutes of the price source and the volatility
+    pub fn get_price_interval(&self) -> Result<u32> {
+        match self.price_interval.as_deref() {
+            None => Ok(1),
+            Some(interval) => interval
+                .parse()
+                .ok()
+                .filter(|interval| OHLC_INTERVALS.contains(interval))
+                .ok_or_else(|| self.invalid_value("price_interval", interval)),
+        }
+    }
+
+    pub fn get_price_source(&self) -> Result<PriceSource> {
+        let interval = self.get_price_interval()?;
+
+        match self.price_source.as_deref() {
+            Some("last") => Ok(PriceSource::Last),
+            Some("bid") => Ok(PriceSource::Bid),
+            Some("mid") => Ok(PriceSource::Mid),
+            Some("vwap") => Ok(PriceSource::Vwap(interval)),
+            None | Some("close") => Ok(PriceSource::Close(interval)),
+            Some(price_source) => Err(self.invalid_value("price_source", price_source)),
+        }
+    }
+
+    /// Average true ranges between the price and the stop, the stop distance follows the
+    /// volatility instead of the fixed offset when it is set
+    pub fn get_atr_multiplier(&self) -> Result<Option<Decimal>> {
+        match self.parse_decimal("atr_multiplier", &self.atr_multiplier)? {
+            Some(multiplier) if multiplier <= Decimal::ZERO => {
+                Err(self.invalid_value("atr_multiplier", &multiplier.to_string()))
+            }
+            multiplier => Ok(multiplier),
+        }
+    }
+
+    /// Benefit over the buy price that places a stop at the break even price before the
+    /// `new_stop_loss` one
+    pub fn get_break_even(&self) -> Result<Option<Decimal>> {
+        self.parse_decimal("break_even", &self.break_even)
+    }
+
+    /// Candles averaged by the average true range
+    pub fn get_atr_period(&self) -> Result<usize> {
+        match self.atr_period.as_deref() {
+            None => Ok(14),
+            Some(period) => period
+                .parse()
+                .ok()
+                .filter(|period| *period > 0)
+                .ok_or_else(|| self.invalid_value("atr_period", period)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AssetPairsCache {
+    pub time: i64,
+    pub asset_pairs: HashMap<String, AssetPair>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PaperOrder {
+    pub txid: String,
+    pub pair: String,
+    pub operation_type: String,
+    pub order_type: String,
+    pub price: String,
+    #[serde(default)]
+    pub price2: Option<String>,
+    pub volume: String,
+    #[serde(default)]
+    pub userref: Option<i32>,
+    pub status: String,
+    pub time: i64,
+}
+
+/// Progress reading the trades history. The backfill reads it from the newest trade to the
+/// oldest one, `offset` being the next page, and `complete` is set once the oldest was read
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct TradesSync {
+    pub offset: u32,
+    pub complete: bool,
+}
+
+/// Outcome of moving a stop loss: `edited` in place, `replaced` by a new order,
+/// `restored` when the previous stop was placed again or `unprotected`
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct StopReplacement {
+    pub txid: String,
+    pub pair: String,
+    pub price: String,
+    pub outcome: String,
+    pub error: Option<String>,
+    pub time: i64,
 }
 
 impl DancespieleDB {
-    pub fn new(url: &str) -> Self {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            db: sled::open(url)?,
+            dry_run: false,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn temporary() -> Self {
         Self {
-            db: sled::open(url).unwrap(),
+            db: sled::Config::new().temporary(true).open().unwrap(),
+            dry_run: false,
+        }
+    }
+
+    /// Keeps the state of the orders the worker placed apart from the live one, so paper
+    /// orders recorded in a dry run do not stand for real orders once it runs live
+    pub fn for_dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    fn state_key(&self, key: &str) -> String {
+        if self.dry_run {
+            format!("dry_run_{}", key)
+        } else {
+            key.to_string()
         }
     }
 
+    #[cfg(test)]
+    pub fn save_coins_percentages_stop_loss(&mut self, percentages: &[Percentage]) {
+        let percentages_string = serde_json::to_string(percentages).unwrap();
+
+        self.db
+            .insert("percentages", percentages_string.as_bytes())
+            .unwrap();
+    }
+
     pub fn fetch_coins_percentages_stop_loss(&mut self) -> Result<Vec<Percentage>> {
-        let percentages = self
-            .db
-            .get("percentages")?
-            .ok_or_else(|| Error::CollectionNotFound(IVec::from("percentages")))?;
+        let percentages = self.db.get("percentages")?.ok_or_else(|| {
+            Error::Config(String::from("percentages are not set in the database"))
+        })?;
 
-        let percentages_string = str::from_utf8(&percentages).unwrap();
+        let percentages_string = str::from_utf8(&percentages)?;
 
-        let response: Vec<Percentage> = serde_json::from_str(percentages_string).unwrap();
+        let response: Vec<Percentage> = serde_json::from_str(percentages_string)?;
 
         Ok(response)
     }
+
+    pub fn fetch_asset_pairs(&mut self) -> Result<Option<AssetPairsCache>> {
+        if let Some(asset_pairs) = self.db.get("asset_pairs")? {
+            let asset_pairs_string = str::from_utf8(&asset_pairs)?;
+
+            let response: AssetPairsCache = serde_json::from_str(asset_pairs_string)?;
+
+            Ok(Some(response))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn save_asset_pairs(&mut self, asset_pairs: &AssetPairsCache) -> Result<()> {
+        let asset_pairs_string = serde_json::to_string(asset_pairs)?;
+
+        self.db
+            .insert("asset_pairs", asset_pairs_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn fetch_trades(&mut self) -> Result<HashMap<String, Trade>> {
+        if let Some(trades) = self.db.get("trades")? {
+            let trades_string = str::from_utf8(&trades)?;
+
+            let response: HashMap<String, Trade> = serde_json::from_str(trades_string)?;
+
+            Ok(response)
+        } else {
+            Ok(HashMap::new())
+        }
+    }
+
+    pub fn save_trades(&mut self, trades: &HashMap<String, Trade>) -> Result<()> {
+        let trades_string = serde_json::to_string(trades)?;
+
+        self.db.insert("trades", trades_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn fetch_trades_sync(&mut self) -> Result<TradesSync> {
+        if let Some(trades_sync) = self.db.get("trades_sync")? {
+            let trades_sync_string = str::from_utf8(&trades_sync)?;
+
+            let response: TradesSync = serde_json::from_str(trades_sync_string)?;
+
+            Ok(response)
+        } else {
+            Ok(TradesSync::default())
+        }
+    }
+
+    pub fn save_trades_sync(&mut self, trades_sync: &TradesSync) -> Result<()> {
+        let trades_sync_string = serde_json::to_string(trades_sync)?;
+
+        self.db
+            .insert("trades_sync", trades_sync_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Operation time of the position each take profit was placed for, so a filled
+    /// take profit is not placed again until the next buy
+    pub fn fetch_take_profits(&mut self) -> Result<HashMap<String, i64>> {
+        if let Some(take_profits) = self.db.get(self.state_key("take_profits"))? {
+            let take_profits_string = str::from_utf8(&take_profits)?;
+
+            let response: HashMap<String, i64> = serde_json::from_str(take_profits_string)?;
+
+            Ok(response)
+        } else {
+            Ok(HashMap::new())
+        }
+    }
+
+    pub fn save_take_profits(&mut self, take_profits: &HashMap<String, i64>) -> Result<()> {
+        let take_profits_string = serde_json::to_string(take_profits)?;
+
+        self.db.insert(
+            self.state_key("take_profits"),
+            take_profits_string.as_bytes(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Positions (buy operation time by pair) whose stop is still the break even one
+    pub fn fetch_break_evens(&mut self) -> Result<HashMap<String, i64>> {
+        if let Some(break_evens) = self.db.get(self.state_key("break_evens"))? {
+            let break_evens_string = str::from_utf8(&break_evens)?;
+
+            let response: HashMap<String, i64> = serde_json::from_str(break_evens_string)?;
+
+            Ok(response)
+        } else {
+            Ok(HashMap::new())
+        }
+    }
+
+    pub fn save_break_evens(&mut self, break_evens: &HashMap<String, i64>) -> Result<()> {
+        let break_evens_string = serde_json::to_string(break_evens)?;
+
+        self.db
+            .insert(self.state_key("break_evens"), break_evens_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn fetch_stop_replacements(&mut self) -> Result<Vec<StopReplacement>> {
+        if let Some(replacements) = self.db.get(self.state_key("stop_replacements"))? {
+            let replacements_string = str::from_utf8(&replacements)?;
+
+            let response: Vec<StopReplacement> = serde_json::from_str(replacements_string)?;
+
+            Ok(response)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    pub fn save_stop_replacements(&mut self, replacements: &[StopReplacement]) -> Result<()> {
+        let replacements_string = serde_json::to_string(replacements)?;
+
+        self.db.insert(
+            self.state_key("stop_replacements"),
+            replacements_string.as_bytes(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Entry price of the positions protected by the worker by pair
+    pub fn fetch_positions(&mut self) -> Result<HashMap<String, Decimal>> {
+        if let Some(positions) = self.db.get(self.state_key("positions"))? {
+            let positions_string = str::from_utf8(&positions)?;
+
+            let response: HashMap<String, Decimal> = serde_json::from_str(positions_string)?;
+
+            Ok(response)
+        } else {
+            Ok(HashMap::new())
+        }
+    }
+
+    pub fn save_positions(&mut self, positions: &HashMap<String, Decimal>) -> Result<()> {
+        let positions_string = serde_json::to_string(positions)?;
+
+        self.db
+            .insert(self.state_key("positions"), positions_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Time until the closed orders were checked for stop loss fills
+    pub fn fetch_fills_checked(&mut self) -> Result<Option<i64>> {
+        if let Some(checked) = self.db.get("fills_checked")? {
+            let checked_string = str::from_utf8(&checked)?;
+
+            Ok(Some(serde_json::from_str(checked_string)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn save_fills_checked(&mut self, checked: i64) -> Result<()> {
+        self.db
+            .insert("fills_checked", checked.to_string().as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn fetch_stop_fills(&mut self) -> Result<Vec<StopFill>> {
+        if let Some(fills) = self.db.get("stop_fills")? {
+            let fills_string = str::from_utf8(&fills)?;
+
+            let response: Vec<StopFill> = serde_json::from_str(fills_string)?;
+
+            Ok(response)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    pub fn save_stop_fills(&mut self, fills: &[StopFill]) -> Result<()> {
+        let fills_string = serde_json::to_string(fills)?;
+
+        self.db.insert("stop_fills", fills_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn fetch_paper_orders(&mut self) -> Result<Vec<PaperOrder>> {
+        if let Some(paper_orders) = self.db.get("paper_orders")? {
+            let paper_orders_string = str::from_utf8(&paper_orders)?;
+
+            let response: Vec<PaperOrder> = serde_json::from_str(paper_orders_string)?;
+
+            Ok(response)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    pub fn save_paper_orders(&mut self, paper_orders: &[PaperOrder]) -> Result<()> {
+        let paper_orders_string = serde_json::to_string(paper_orders)?;
+
+        self.db
+            .insert("paper_orders", paper_orders_string.as_bytes())?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{DancespieleDB, Percentage};
+    use super::{DancespieleDB, PaperOrder, Percentage};
+    use crate::exchange::types::PriceSource;
+    use rust_decimal::Decimal;
 
     #[test]
     fn should_fetch_coins_percentages_stop_loss() {
@@ -45,17 +482,19 @@ mod tests {
                 new_stop_loss: String::from("15.0"),
                 next_stop_loss: String::from("5.0"),
                 pair: String::from("KAVAEUR"),
+                ..Percentage::default()
             },
             Percentage {
                 new_stop_loss: String::from("30.0"),
                 next_stop_loss: String::from("5.0"),
                 pair: String::from("OXTEUR"),
+                ..Percentage::default()
             },
         ];
 
         let percentages_string = serde_json::to_string(&percentages).unwrap();
 
-        let mut dancespiele_db = DancespieleDB::new("test_sled");
+        let mut dancespiele_db = DancespieleDB::new("test_sled").unwrap();
 
         dancespiele_db
             .db
@@ -71,4 +510,128 @@ mod tests {
             serde_json::to_string(&percentages).unwrap()
         );
     }
+
+    #[test]
+    fn should_reject_invalid_pair_config() {
+        let percentage = Percentage {
+            new_stop_loss: String::from("15.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("KAVAEUR"),
+            order_type: Some(String::from("stop-loss-limt")),
+            limit_offset: Some(String::from("1,5")),
+            take_profit_order_type: Some(String::from("take-profit-limit")),
+            ..Percentage::default()
+        };
+
+        assert!(percentage.get_order_type().is_err());
+        assert!(percentage.get_limit_offset(Decimal::new(1, 0)).is_err());
+        assert!(percentage.get_take_profit().unwrap().is_none());
+        assert!(percentage.get_take_profit_order_type().is_ok());
+        assert!(Percentage {
+            break_even: Some(String::from("5%")),
+            ..Percentage::default()
+        }
+        .get_break_even()
+        .is_err());
+    }
+
+    #[test]
+    fn should_reject_invalid_volatility_config() {
+        let percentage = Percentage {
+            pair: String::from("KAVAEUR"),
+            atr_multiplier: Some(String::from("two")),
+            atr_period: Some(String::from("0")),
+            ..Percentage::default()
+        };
+
+        assert!(percentage.get_atr_multiplier().is_err());
+        assert!(percentage.get_atr_period().is_err());
+        assert_eq!(Percentage::default().get_atr_multiplier().unwrap(), None);
+        assert_eq!(Percentage::default().get_atr_period().unwrap(), 14);
+    }
+
+    #[test]
+    fn should_reject_unknown_price_source() {
+        let percentage = Percentage {
+            pair: String::from("KAVAEUR"),
+            price_source: Some(String::from("vwap")),
+            price_interval: Some(String::from("15")),
+            ..Percentage::default()
+        };
+
+        assert_eq!(
+            percentage.get_price_source().unwrap(),
+            PriceSource::Vwap(15)
+        );
+        assert!(Percentage {
+            price_source: Some(String::from("ask")),
+            ..percentage.clone()
+        }
+        .get_price_source()
+        .is_err());
+        assert!(Percentage {
+            price_interval: Some(String::from("1h")),
+            ..percentage.clone()
+        }
+        .get_price_source()
+        .is_err());
+        assert!(Percentage {
+            price_interval: Some(String::from("2")),
+            ..percentage
+        }
+        .get_price_source()
+        .is_err());
+    }
+
+    #[test]
+    fn should_read_stop_loss_offset_compatibly() {
+        let percentages: Vec<Percentage> = serde_json::from_str(
+            r#"[
+                {"pair":"KAVAEUR","new_stop_loss":"15.0","next_stop_loss":"5.0"},
+                {"pair":"OXTEUR","new_stop_loss":"30.0","next_stop_loss":"5.0","stop_loss_offset":"5.0"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            percentages[0]
+                .get_stop_loss_offset(Decimal::new(2, 0))
+                .unwrap(),
+            Decimal::new(2, 0)
+        );
+        assert_eq!(
+            percentages[1]
+                .get_stop_loss_offset(Decimal::new(2, 0))
+                .unwrap(),
+            Decimal::new(50, 1)
+        );
+        assert_eq!(
+            serde_json::to_string(&percentages[0]).unwrap(),
+            r#"{"pair":"KAVAEUR","new_stop_loss":"15.0","next_stop_loss":"5.0"}"#
+        );
+    }
+
+    #[test]
+    fn should_save_paper_orders() {
+        let paper_orders = vec![PaperOrder {
+            txid: String::from("PAPER-1"),
+            pair: String::from("KAVAEUR"),
+            operation_type: String::from("sell"),
+            order_type: String::from("stop-loss"),
+            price: String::from("3.43"),
+            price2: None,
+            volume: String::from("1500"),
+            userref: None,
+            status: String::from("open"),
+            time: 160000,
+        }];
+
+        let mut dancespiele_db = DancespieleDB::temporary();
+
+        assert!(dancespiele_db.fetch_paper_orders().unwrap().is_empty());
+
+        dancespiele_db.save_paper_orders(&paper_orders).unwrap();
+
+        assert_eq!(dancespiele_db.fetch_paper_orders().unwrap(), paper_orders);
+    }
 }
@@ -0,0 +1,281 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug)]
+pub struct CostBasis {
+    pub pair: String,
+    pub cost: Decimal,
+    pub fees: Decimal,
+    pub quantity: Decimal,
+    pub average_price: Decimal,
+    pub operation_time: i64,
+}
+
+impl From<String> for CostBasis {
+    fn from(pair: String) -> Self {
+        Self {
+            pair,
+            cost: Decimal::ZERO,
+            fees: Decimal::ZERO,
+            quantity: Decimal::ZERO,
+            average_price: Decimal::ZERO,
+            operation_time: 0,
+        }
+    }
+}
+
+impl CostBasis {
+    /// Price covering the fees paid to buy and the same fees again to sell
+    pub fn calc_break_even_price(&self) -> Decimal {
+        if self.quantity.is_zero() {
+            Decimal::ZERO
+        } else {
+            (self.cost + self.fees * Decimal::new(2, 0)) / self.quantity
+        }
+    }
+
+    pub fn calc_average_price(&self, include_fees: bool) -> Decimal {
+        if self.quantity.is_zero() {
+            Decimal::ZERO
+        } else if include_fees {
+            (self.cost + self.fees) / self.quantity
+        } else {
+            self.cost / self.quantity
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FutureOperation {
+    pub buy_price: Decimal,
+    pub pair: String,
+    pub quantity: Decimal,
+    pub operation_time: i64,
+    pub break_even_price: Decimal,
+}
+
+impl From<(CostBasis, Decimal)> for FutureOperation {
+    fn from(future_operation: (CostBasis, Decimal)) -> Self {
+        let (cost_basis, quantity) = future_operation;
+        let break_even_price = cost_basis.calc_break_even_price();
+
+        Self {
+            buy_price: cost_basis.average_price,
+            pair: cost_basis.pair,
+            quantity,
+            operation_time: cost_basis.operation_time,
+            break_even_price,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CurrentPrice {
+    pub pair: String,
+    pub price: Decimal,
+}
+
+impl From<(String, Decimal)> for CurrentPrice {
+    fn from(current_price: (String, Decimal)) -> Self {
+        let (pair, price) = current_price;
+
+        Self { pair, price }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StopLossActive {
+    pub order: String,
+    pub price: Decimal,
+    pub pair: String,
+    pub current_price: Decimal,
+}
+
+impl From<(String, Decimal, CurrentPrice)> for StopLossActive {
+    fn from(stop_loss_active: (String, Decimal, CurrentPrice)) -> Self {
+        let (order, price, current_price) = stop_loss_active;
+
+        Self {
+            order,
+            price,
+            pair: current_price.pair,
+            current_price: current_price.price,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Notify {
+    pub pair: String,
+    pub price: String,
+    pub benefit: String,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NotifyEmail {
+    pub pair: String,
+    pub price: String,
+    pub benefit: String,
+    pub email: String,
+    pub dry_run: bool,
+}
+
+impl From<(Notify, String)> for NotifyEmail {
+    fn from(notify: (Notify, String)) -> Self {
+        let (content, email) = notify;
+
+        Self {
+            price: content.price,
+            pair: content.pair,
+            benefit: content.benefit,
+            email,
+            dry_run: content.dry_run,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotifyFill {
+    pub pair: String,
+    pub price: String,
+    pub volume: String,
+    pub pnl: Option<String>,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NotifyFillEmail {
+    pub pair: String,
+    pub price: String,
+    pub volume: String,
+    pub pnl: Option<String>,
+    pub email: String,
+    pub dry_run: bool,
+}
+
+impl From<(NotifyFill, String)> for NotifyFillEmail {
+    fn from(notify: (NotifyFill, String)) -> Self {
+        let (content, email) = notify;
+
+        Self {
+            pair: content.pair,
+            price: content.price,
+            volume: content.volume,
+            pnl: content.pnl,
+            email,
+            dry_run: content.dry_run,
+        }
+    }
+}
+
+impl From<(&StopFill, bool)> for NotifyFill {
+    fn from(notify: (&StopFill, bool)) -> Self {
+        let (fill, dry_run) = notify;
+
+        Self {
+            pair: fill.pair.clone(),
+            price: fill.price.to_string(),
+            volume: fill.volume.to_string(),
+            pnl: fill.pnl.map(|pnl| pnl.to_string()),
+            dry_run,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    None,
+    Placed,
+    Moved,
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PairReport {
+    pub pair: String,
+    /// Price the benefit is calculated from, the current stop loss or the buy price
+    pub reference_price: Decimal,
+    pub price_bought: Decimal,
+    pub current_price: Decimal,
+    pub benefit: Decimal,
+    pub current_stop_loss: Option<Decimal>,
+    pub new_stop_loss: Option<Decimal>,
+    pub action: Action,
+    pub error: Option<String>,
+    pub foreign_stop_losses: Vec<String>,
+}
+
+impl From<(CurrentPrice, Decimal, Decimal, Option<Decimal>)> for PairReport {
+    fn from(report: (CurrentPrice, Decimal, Decimal, Option<Decimal>)) -> Self {
+        let (current_price, price_bought, benefit, current_stop_loss) = report;
+
+        Self {
+            pair: current_price.pair,
+            reference_price: current_stop_loss.unwrap_or(price_bought),
+            price_bought,
+            current_price: current_price.price,
+            benefit,
+            current_stop_loss,
+            new_stop_loss: None,
+            action: Action::None,
+            error: None,
+            foreign_stop_losses: vec![],
+        }
+    }
+}
+
+/// Stop loss of the worker executed by the exchange, `pnl` is the realized profit of the
+/// position when its entry price was known
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StopFill {
+    pub txid: String,
+    pub pair: String,
+    pub price: Decimal,
+    pub volume: Decimal,
+    pub fee: Decimal,
+    pub entry_price: Option<Decimal>,
+    pub pnl: Option<Decimal>,
+    pub time: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TickReport {
+    pub tick_id: String,
+    pub time: i64,
+    pub dry_run: bool,
+    pub pairs: Vec<PairReport>,
+    #[serde(default)]
+    pub fills: Vec<StopFill>,
+}
+
+impl From<(String, i64, bool, Vec<PairReport>)> for TickReport {
+    fn from(report: (String, i64, bool, Vec<PairReport>)) -> Self {
+        let (tick_id, time, dry_run, pairs) = report;
+
+        Self {
+            tick_id,
+            time,
+            dry_run,
+            pairs,
+            fills: vec![],
+        }
+    }
+}
+
+impl From<(&PairReport, bool)> for Notify {
+    fn from(notify: (&PairReport, bool)) -> Self {
+        let (report, dry_run) = notify;
+
+        Self {
+            pair: report.pair.clone(),
+            price: report
+                .new_stop_loss
+                .map(|price| price.to_string())
+                .unwrap_or_default(),
+            benefit: report.benefit.to_string(),
+            dry_run,
+        }
+    }
+}
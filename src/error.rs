@@ -0,0 +1,95 @@
+use coinnect::error::ErrorKind;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Failures of a worker tick, grouped by the system that produced them
+#[derive(Debug)]
+pub enum Error {
+    Exchange(coinnect::error::Error),
+    Db(String),
+    Config(String),
+    Notification(String),
+    Parse(String),
+    Stream(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Exchange(err) => write!(f, "exchange error: {}", err),
+            Error::Db(err) => write!(f, "database error: {}", err),
+            Error::Config(err) => write!(f, "configuration error: {}", err),
+            Error::Notification(err) => write!(f, "notification error: {}", err),
+            Error::Parse(err) => write!(f, "parse error: {}", err),
+            Error::Stream(err) => write!(f, "stream error: {}", err),
+        }
+    }
+}
+
+impl Error {
+    /// Label of the exchange failure, `None` for errors that did not come from the exchange
+    pub fn exchange_kind(&self) -> Option<&'static str> {
+        match self {
+            Error::Exchange(err) => Some(match err.kind() {
+                ErrorKind::ServiceUnavailable(_) => "service_unavailable",
+                ErrorKind::BadCredentials => "bad_credentials",
+                ErrorKind::RateLimitExceeded => "rate_limit_exceeded",
+                ErrorKind::InvalidNonce => "invalid_nonce",
+                ErrorKind::PermissionDenied => "permission_denied",
+                ErrorKind::InvalidArguments => "invalid_arguments",
+                ErrorKind::ExchangeSpecificError(_) => "exchange_specific",
+                ErrorKind::MissingField(_) | ErrorKind::MissingPrice => "missing_data",
+                ErrorKind::BadParse
+                | ErrorKind::InvalidFieldFormat(_)
+                | ErrorKind::InvalidFieldValue(_) => "bad_response",
+                _ => "other",
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<coinnect::error::Error> for Error {
+    fn from(err: coinnect::error::Error) -> Self {
+        Error::Exchange(err)
+    }
+}
+
+impl From<sled::Error> for Error {
+    fn from(err: sled::Error) -> Self {
+        Error::Db(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(err: std::str::Utf8Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(err: tungstenite::Error) -> Self {
+        Error::Stream(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Stream(err.to_string())
+    }
+}
+
+impl From<rust_decimal::Error> for Error {
+    fn from(err: rust_decimal::Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
@@ -0,0 +1,487 @@
+use super::types::{
+    AssetPair, ClosedOrders, Ohlc, OpenOrders, Order, OrderRequest, PriceSource, Trade, Trades,
+};
+use super::Exchange;
+use crate::error::{Error, Result};
+use crate::utils::normalize_asset;
+use coinnect::error::ErrorKind;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+
+/// In memory exchange with scriptable balances, trades, open orders and prices.
+/// Every order placed or cancelled is recorded so tests can assert what the worker sent.
+#[derive(Default)]
+pub struct MockExchange {
+    balances: HashMap<String, String>,
+    trades: HashMap<String, Trade>,
+    open_orders: HashMap<String, Order>,
+    closed_orders: HashMap<String, Order>,
+    prices: HashMap<String, VecDeque<String>>,
+    tickers: HashMap<String, (String, String, String)>,
+    candles: HashMap<String, Vec<Ohlc>>,
+    ohlc_requests: u32,
+    asset_pairs: HashMap<String, AssetPair>,
+    placed: Vec<OrderRequest>,
+    cancelled: Vec<String>,
+    order_count: u32,
+    page_size: usize,
+    trades_offsets: Vec<u32>,
+    order_editing: bool,
+    closed_orders_failing: bool,
+    trades_failing_from: Option<u32>,
+    edited: Vec<(String, OrderRequest)>,
+    rejected_prices: Vec<String>,
+}
+
+impl MockExchange {
+    pub fn new() -> Self {
+        Self {
+            page_size: 50,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_order_editing(mut self) -> Self {
+        self.order_editing = true;
+        self
+    }
+
+    /// Pages of the trades history from `offset` can not be read, as when the exchange
+    /// rate limits a long backfill
+    pub fn with_trades_failing_from(mut self, offset: u32) -> Self {
+        self.trades_failing_from = Some(offset);
+        self
+    }
+
+    /// Closed orders can not be read, as when the exchange rate limits the call
+    pub fn with_closed_orders_failing(mut self) -> Self {
+        self.closed_orders_failing = true;
+        self
+    }
+
+    /// Orders placed at `price` are rejected by the exchange
+    pub fn with_rejected_price(mut self, price: &str) -> Self {
+        self.rejected_prices.push(price.to_string());
+        self
+    }
+
+    pub fn with_balance(mut self, asset: &str, quantity: &str) -> Self {
+        self.balances
+            .insert(asset.to_string(), quantity.to_string());
+        self
+    }
+
+    pub fn with_trade(
+        mut self,
+        pair: &str,
+        trade_type: &str,
+        price: &str,
+        vol: &str,
+        time: f64,
+    ) -> Self {
+        let id = format!("TMOCK-{}", self.trades.len() + 1);
+
+        self.trades.insert(
+            id,
+            Trade {
+                pair: pair.to_string(),
+                trade_type: trade_type.to_string(),
+                price: price.to_string(),
+                volume: vol.to_string(),
+                fee: String::from("0.0"),
+                time,
+            },
+        );
+        self
+    }
+
+    pub fn with_open_order(mut self, txid: &str, order: OrderRequest) -> Self {
+        self.open_orders
+            .insert(txid.to_string(), Order::from(order));
+        self
+    }
+
+    /// Order executed completely at `price` on `close_time`
+    pub fn with_closed_order(
+        mut self,
+        txid: &str,
+        order: OrderRequest,
+        price: &str,
+        close_time: f64,
+    ) -> Self {
+        let mut closed_order = Order::from(order);
+
+        closed_order.status = String::from("closed");
+        closed_order.average_price = price.to_string();
+        closed_order.volume_executed = closed_order.volume.clone();
+        closed_order.close_time = Some(close_time);
+
+        self.closed_orders.insert(txid.to_string(), closed_order);
+        self
+    }
+
+    pub fn with_executed_volume(mut self, txid: &str, volume_executed: &str) -> Self {
+        if let Some(order) = self.open_orders.get_mut(txid) {
+            order.volume_executed = volume_executed.to_string();
+        }
+        self
+    }
+
+    pub fn with_asset_pair(
+        mut self,
+        pair: &str,
+        base: &str,
+        quote: &str,
+        pair_decimals: u32,
+        lot_decimals: u32,
+        ordermin: &str,
+    ) -> Self {
+        self.asset_pairs.insert(
+            pair.to_string(),
+            AssetPair {
+                altname: format!("{}{}", normalize_asset(base), normalize_asset(quote)),
+                base: base.to_string(),
+                quote: quote.to_string(),
+                pair_decimals,
+                lot_decimals,
+                ordermin: Some(ordermin.to_string()),
+                tick_size: None,
+                wsname: Some(format!(
+                    "{}/{}",
+                    normalize_asset(base),
+                    normalize_asset(quote)
+                )),
+            },
+        );
+        self
+    }
+
+    /// Bid, ask and last trade read by the ticker price sources instead of the price series
+    pub fn with_ticker(mut self, pair: &str, bid: &str, ask: &str, last: &str) -> Self {
+        self.tickers.insert(
+            pair.to_string(),
+            (bid.to_string(), ask.to_string(), last.to_string()),
+        );
+        self
+    }
+
+    /// Candles of every interval as `(high, low, close)`, the oldest first
+    pub fn with_candles(mut self, pair: &str, candles: &[(&str, &str, &str)]) -> Self {
+        self.candles.insert(
+            pair.to_string(),
+            candles
+                .iter()
+                .enumerate()
+                .map(|(index, (high, low, close))| {
+                    let close = Decimal::from_str(close).unwrap_or(Decimal::ZERO);
+
+                    Ohlc {
+                        time: index as i64 * 60,
+                        open: close,
+                        high: Decimal::from_str(high).unwrap_or(Decimal::ZERO),
+                        low: Decimal::from_str(low).unwrap_or(Decimal::ZERO),
+                        close,
+                        vwap: close,
+                        volume: Decimal::ZERO,
+                    }
+                })
+                .collect(),
+        );
+        self
+    }
+
+    /// Each call to `get_price` consumes the next price of the series,
+    /// the last one is kept once the series is exhausted
+    pub fn with_prices(mut self, pair: &str, prices: &[&str]) -> Self {
+        self.prices.insert(
+            pair.to_string(),
+            prices.iter().map(|price| price.to_string()).collect(),
+        );
+        self
+    }
+
+    pub fn ohlc_requests(&self) -> u32 {
+        self.ohlc_requests
+    }
+
+    pub fn placed_orders(&self) -> Vec<OrderRequest> {
+        self.placed.clone()
+    }
+
+    pub fn cancelled_orders(&self) -> Vec<String> {
+        self.cancelled.clone()
+    }
+
+    pub fn open_orders(&self) -> HashMap<String, Order> {
+        self.open_orders.clone()
+    }
+
+    pub fn trades_offsets(&self) -> Vec<u32> {
+        self.trades_offsets.clone()
+    }
+
+    pub fn edited_orders(&self) -> Vec<(String, OrderRequest)> {
+        self.edited.clone()
+    }
+}
+
+fn exchange_error(kind: ErrorKind) -> Error {
+    coinnect::error::Error::from_kind(kind).into()
+}
+
+impl Exchange for MockExchange {
+    fn get_current_balance(&mut self) -> Result<HashMap<String, String>> {
+        Ok(self.balances.clone())
+    }
+
+    fn get_trades(&mut self, offset: u32) -> Result<Trades> {
+        if self
+            .trades_failing_from
+            .map(|failing_from| offset >= failing_from)
+            .unwrap_or(false)
+        {
+            return Err(exchange_error(ErrorKind::RateLimitExceeded));
+        }
+
+        self.trades_offsets.push(offset);
+
+        let mut trades: Vec<(String, Trade)> = self.trades.clone().into_iter().collect();
+        trades.sort_by(|(id_a, a), (id_b, b)| {
+            b.time
+                .partial_cmp(&a.time)
+                .unwrap_or(Ordering::Equal)
+                .then(id_b.cmp(id_a))
+        });
+
+        Ok(Trades {
+            count: self.trades.len() as u32,
+            trades: trades
+                .into_iter()
+                .skip(offset as usize)
+                .take(self.page_size)
+                .collect(),
+        })
+    }
+
+    fn get_active_orders(&mut self) -> Result<OpenOrders> {
+        Ok(OpenOrders {
+            open: self.open_orders.clone(),
+        })
+    }
+
+    fn get_closed_orders(&mut self, start: i64, offset: u32) -> Result<ClosedOrders> {
+        if self.closed_orders_failing {
+            return Err(exchange_error(ErrorKind::RateLimitExceeded));
+        }
+
+        let mut closed: Vec<(String, Order)> = self
+            .closed_orders
+            .clone()
+            .into_iter()
+            .filter(|(_txid, order)| order.close_time.unwrap_or(0.0) >= start as f64)
+            .collect();
+        closed.sort_by(|(txid_a, a), (txid_b, b)| {
+            b.close_time
+                .partial_cmp(&a.close_time)
+                .unwrap_or(Ordering::Equal)
+                .then(txid_b.cmp(txid_a))
+        });
+
+        Ok(ClosedOrders {
+            count: closed.len() as u32,
+            closed: closed
+                .into_iter()
+                .skip(offset as usize)
+                .take(self.page_size)
+                .collect(),
+        })
+    }
+
+    fn get_price(&mut self, pair: &str, source: PriceSource) -> Result<String> {
+        if let Some((bid, ask, last)) = self.tickers.get(pair) {
+            match source {
+                PriceSource::Last => return Ok(last.clone()),
+                PriceSource::Bid => return Ok(bid.clone()),
+                PriceSource::Mid => {
+                    let bid =
+                        Decimal::from_str(bid).map_err(|_| exchange_error(ErrorKind::BadParse))?;
+                    let ask =
+                        Decimal::from_str(ask).map_err(|_| exchange_error(ErrorKind::BadParse))?;
+
+                    return Ok(((ask + bid) / Decimal::new(2, 0)).normalize().to_string());
+                }
+                _ => (),
+            }
+        }
+
+        let prices = self
+            .prices
+            .get_mut(pair)
+            .ok_or_else(|| exchange_error(ErrorKind::MissingField(pair.to_string())))?;
+
+        let price = if prices.len() > 1 {
+            prices.pop_front()
+        } else {
+            prices.front().cloned()
+        };
+
+        price.ok_or_else(|| exchange_error(ErrorKind::MissingPrice))
+    }
+
+    fn get_ohlc(&mut self, pair: &str, _interval: u32) -> Result<Vec<Ohlc>> {
+        self.ohlc_requests += 1;
+
+        self.candles
+            .get(pair)
+            .cloned()
+            .ok_or_else(|| exchange_error(ErrorKind::MissingField(pair.to_string())))
+    }
+
+    fn get_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>> {
+        Ok(self.asset_pairs.clone())
+    }
+
+    fn add_order(&mut self, order: OrderRequest) -> Result<()> {
+        if self.rejected_prices.contains(&order.price) {
+            return Err(exchange_error(ErrorKind::ExchangeSpecificError(
+                String::from("EOrder:Invalid price"),
+            )));
+        }
+
+        self.order_count += 1;
+        let txid = format!("OMOCK-{}", self.order_count);
+
+        self.open_orders.insert(txid, Order::from(order.clone()));
+        self.placed.push(order);
+
+        Ok(())
+    }
+
+    fn cancel_order(&mut self, order: &str) -> Result<()> {
+        self.open_orders
+            .remove(order)
+            .ok_or_else(|| exchange_error(ErrorKind::InvalidArguments))?;
+        self.cancelled.push(order.to_string());
+
+        Ok(())
+    }
+
+    fn edit_order(&mut self, txid: &str, order: OrderRequest) -> Result<bool> {
+        if !self.order_editing {
+            return Ok(false);
+        }
+
+        if !self.open_orders.contains_key(txid) || self.rejected_prices.contains(&order.price) {
+            return Err(exchange_error(ErrorKind::InvalidArguments));
+        }
+
+        self.open_orders
+            .insert(txid.to_string(), Order::from(order.clone()));
+        self.edited.push((txid.to_string(), order));
+
+        Ok(true)
+    }
+
+    fn name(&self) -> String {
+        String::from("mock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MockExchange;
+    use crate::exchange::types::{OrderRequest, PriceSource};
+    use crate::exchange::Exchange;
+
+    #[test]
+    fn should_consume_price_series() {
+        let mut mock_exchange = MockExchange::new().with_prices("KAVAEUR", &["3.0", "3.5"]);
+
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::default())
+                .unwrap(),
+            "3.0"
+        );
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::default())
+                .unwrap(),
+            "3.5"
+        );
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::default())
+                .unwrap(),
+            "3.5"
+        );
+        assert!(mock_exchange
+            .get_price("OXTEUR", PriceSource::default())
+            .is_err());
+    }
+
+    #[test]
+    fn should_read_ticker_price_sources() {
+        let mut mock_exchange = MockExchange::new()
+            .with_prices("KAVAEUR", &["3.0"])
+            .with_ticker("KAVAEUR", "3.40", "3.50", "3.46");
+
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::Bid)
+                .unwrap(),
+            "3.40"
+        );
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::Mid)
+                .unwrap(),
+            "3.45"
+        );
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::Last)
+                .unwrap(),
+            "3.46"
+        );
+        assert_eq!(
+            mock_exchange
+                .get_price("KAVAEUR", PriceSource::Close(60))
+                .unwrap(),
+            "3.0"
+        );
+    }
+
+    #[test]
+    fn should_keep_order_book_of_placed_and_cancelled_orders() {
+        let order = OrderRequest {
+            pair: String::from("KAVAEUR"),
+            operation_type: String::from("sell"),
+            order_type: String::from("stop-loss"),
+            price: String::from("3.43"),
+            price2: None,
+            volume: String::from("1500"),
+            userref: None,
+        };
+
+        let mut mock_exchange = MockExchange::new().with_open_order("3344de344", order.clone());
+
+        mock_exchange.cancel_order("3344de344").unwrap();
+        mock_exchange.add_order(order.clone()).unwrap();
+
+        assert!(mock_exchange.cancel_order("3344de344").is_err());
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec!["3344de344".to_string()]
+        );
+        assert_eq!(mock_exchange.placed_orders(), vec![order]);
+        assert!(mock_exchange.open_orders().contains_key("OMOCK-1"));
+    }
+}
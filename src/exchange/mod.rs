@@ -0,0 +1,109 @@
+#[cfg(test)]
+pub mod mock;
+mod paper;
+mod streaming;
+pub mod types;
+
+pub use paper::PaperExchange;
+pub use streaming::StreamingExchange;
+
+use crate::error::Result;
+use std::collections::HashMap;
+use types::{AssetPair, ClosedOrders, Ohlc, OpenOrders, OrderRequest, PriceSource, Trades};
+
+/// Operations that the stop loss brain needs from an exchange platform
+pub trait Exchange {
+    fn get_current_balance(&mut self) -> Result<HashMap<String, String>>;
+
+    /// Page of the trades history starting at `offset`, newest first
+    fn get_trades(&mut self, offset: u32) -> Result<Trades>;
+
+    fn get_active_orders(&mut self) -> Result<OpenOrders>;
+
+    /// Page of the orders closed since `start` (unix time) starting at `offset`
+    fn get_closed_orders(&mut self, _start: i64, _offset: u32) -> Result<ClosedOrders> {
+        Ok(ClosedOrders::default())
+    }
+
+    fn get_price(&mut self, pair: &str, source: PriceSource) -> Result<String>;
+
+    /// Candles of the interval in minutes, the oldest first
+    fn get_ohlc(&mut self, pair: &str, interval: u32) -> Result<Vec<Ohlc>>;
+
+    fn get_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>>;
+
+    fn add_order(&mut self, order: OrderRequest) -> Result<()>;
+
+    fn cancel_order(&mut self, order: &str) -> Result<()>;
+
+    /// Checks the order against the exchange without placing it
+    fn validate_order(&mut self, _order: OrderRequest) -> Result<()> {
+        Ok(())
+    }
+
+    /// Amends an open order in a single call, `false` when the exchange cannot edit orders
+    fn edit_order(&mut self, _txid: &str, _order: OrderRequest) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn dry_run(&self) -> bool {
+        false
+    }
+
+    /// Name of the platform, added to the logs of every tick
+    fn name(&self) -> String;
+}
+
+impl<E: Exchange + ?Sized> Exchange for &mut E {
+    fn get_current_balance(&mut self) -> Result<HashMap<String, String>> {
+        (**self).get_current_balance()
+    }
+
+    fn get_trades(&mut self, offset: u32) -> Result<Trades> {
+        (**self).get_trades(offset)
+    }
+
+    fn get_active_orders(&mut self) -> Result<OpenOrders> {
+        (**self).get_active_orders()
+    }
+
+    fn get_closed_orders(&mut self, start: i64, offset: u32) -> Result<ClosedOrders> {
+        (**self).get_closed_orders(start, offset)
+    }
+
+    fn get_price(&mut self, pair: &str, source: PriceSource) -> Result<String> {
+        (**self).get_price(pair, source)
+    }
+
+    fn get_ohlc(&mut self, pair: &str, interval: u32) -> Result<Vec<Ohlc>> {
+        (**self).get_ohlc(pair, interval)
+    }
+
+    fn get_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>> {
+        (**self).get_asset_pairs()
+    }
+
+    fn add_order(&mut self, order: OrderRequest) -> Result<()> {
+        (**self).add_order(order)
+    }
+
+    fn cancel_order(&mut self, order: &str) -> Result<()> {
+        (**self).cancel_order(order)
+    }
+
+    fn validate_order(&mut self, order: OrderRequest) -> Result<()> {
+        (**self).validate_order(order)
+    }
+
+    fn edit_order(&mut self, txid: &str, order: OrderRequest) -> Result<bool> {
+        (**self).edit_order(txid, order)
+    }
+
+    fn dry_run(&self) -> bool {
+        (**self).dry_run()
+    }
+
+    fn name(&self) -> String {
+        (**self).name()
+    }
+}
@@ -0,0 +1,243 @@
+use super::types::{
+    AssetPair, ClosedOrders, Ohlc, OpenOrders, Order, OrderRequest, PriceSource, Trades,
+};
+use super::Exchange;
+use crate::db::{DancespieleDB, PaperOrder};
+use crate::error::Result;
+use crate::logger;
+use chrono::Utc;
+use coinnect::error::Error;
+use std::collections::HashMap;
+
+/// Paper trading exchange: balances, trades and prices are read from the real exchange
+/// while orders are validated and recorded in the sled ledger instead of being sent.
+pub struct PaperExchange<E: Exchange> {
+    exchange: E,
+    dancespiele_db: DancespieleDB,
+}
+
+impl<E: Exchange> PaperExchange<E> {
+    pub fn new(exchange: E, dancespiele_db: DancespieleDB) -> Self {
+        Self {
+            exchange,
+            dancespiele_db,
+        }
+    }
+}
+
+impl<E: Exchange> Exchange for PaperExchange<E> {
+    fn get_current_balance(&mut self) -> Result<HashMap<String, String>> {
+        self.exchange.get_current_balance()
+    }
+
+    fn get_trades(&mut self, offset: u32) -> Result<Trades> {
+        self.exchange.get_trades(offset)
+    }
+
+    fn get_active_orders(&mut self) -> Result<OpenOrders> {
+        let mut open_orders = self.exchange.get_active_orders()?;
+
+        self.dancespiele_db
+            .fetch_paper_orders()?
+            .into_iter()
+            .for_each(|paper_order| {
+                if paper_order.status == "open" {
+                    open_orders.open.insert(
+                        paper_order.txid.clone(),
+                        Order::from(OrderRequest::from(paper_order)),
+                    );
+                } else {
+                    open_orders.open.remove(&paper_order.txid);
+                }
+            });
+
+        Ok(open_orders)
+    }
+
+    fn get_closed_orders(&mut self, start: i64, offset: u32) -> Result<ClosedOrders> {
+        self.exchange.get_closed_orders(start, offset)
+    }
+
+    fn get_price(&mut self, pair: &str, source: PriceSource) -> Result<String> {
+        self.exchange.get_price(pair, source)
+    }
+
+    fn get_ohlc(&mut self, pair: &str, interval: u32) -> Result<Vec<Ohlc>> {
+        self.exchange.get_ohlc(pair, interval)
+    }
+
+    fn get_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>> {
+        self.exchange.get_asset_pairs()
+    }
+
+    fn add_order(&mut self, order: OrderRequest) -> Result<()> {
+        self.exchange.validate_order(order.clone())?;
+
+        let mut paper_orders = self.dancespiele_db.fetch_paper_orders()?;
+        let paper_order = PaperOrder::from((
+            format!("PAPER-{}", paper_orders.len() + 1),
+            order,
+            String::from("open"),
+            Utc::now().timestamp(),
+        ));
+
+        logger::info("Dry run order recorded instead of placed")
+            .pair(&paper_order.pair)
+            .order(&paper_order.txid)
+            .field("operation_type", &paper_order.operation_type)
+            .field("order_type", &paper_order.order_type)
+            .field("volume", &paper_order.volume)
+            .field("price", &paper_order.price)
+            .emit();
+
+        paper_orders.push(paper_order);
+        self.dancespiele_db.save_paper_orders(&paper_orders)
+    }
+
+    fn cancel_order(&mut self, order: &str) -> Result<()> {
+        let mut paper_orders = self.dancespiele_db.fetch_paper_orders()?;
+
+        if let Some(paper_order) = paper_orders.iter_mut().find(|po| po.txid == order) {
+            paper_order.status = String::from("canceled");
+        } else {
+            let real_order = self
+                .exchange
+                .get_active_orders()?
+                .open
+                .remove(order)
+                .ok_or_else(|| Error::from(format!("order {} is not open", order)))?;
+
+            paper_orders.push(PaperOrder::from((
+                order.to_string(),
+                OrderRequest::from(real_order),
+                String::from("canceled"),
+                Utc::now().timestamp(),
+            )));
+        }
+
+        logger::info("Dry run order cancelled instead of sent")
+            .order(order)
+            .emit();
+
+        self.dancespiele_db.save_paper_orders(&paper_orders)
+    }
+
+    fn dry_run(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> String {
+        self.exchange.name()
+    }
+}
+
+impl From<(String, OrderRequest, String, i64)> for PaperOrder {
+    fn from(paper_order: (String, OrderRequest, String, i64)) -> Self {
+        let (txid, order, status, time) = paper_order;
+
+        Self {
+            txid,
+            pair: order.pair,
+            operation_type: order.operation_type,
+            order_type: order.order_type,
+            price: order.price,
+            price2: order.price2,
+            volume: order.volume,
+            userref: order.userref,
+            status,
+            time,
+        }
+    }
+}
+
+impl From<PaperOrder> for OrderRequest {
+    fn from(paper_order: PaperOrder) -> Self {
+        Self {
+            pair: paper_order.pair,
+            operation_type: paper_order.operation_type,
+            order_type: paper_order.order_type,
+            price: paper_order.price,
+            price2: paper_order.price2,
+            volume: paper_order.volume,
+            userref: paper_order.userref,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PaperExchange;
+    use crate::db::{DancespieleDB, Percentage};
+    use crate::exchange::mock::MockExchange;
+    use crate::exchange::types::OrderRequest;
+    use crate::exchange::Exchange;
+    use crate::worker::Worker;
+
+    fn stop_loss(pair: &str, price: &str, volume: &str) -> OrderRequest {
+        OrderRequest {
+            pair: pair.to_string(),
+            operation_type: String::from("sell"),
+            order_type: String::from("stop-loss"),
+            price: price.to_string(),
+            price2: None,
+            volume: volume.to_string(),
+            userref: None,
+        }
+    }
+
+    #[test]
+    fn should_record_orders_in_ledger_without_sending_them() {
+        let mut mock_exchange =
+            MockExchange::new().with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"));
+        let mut dancespiele_db = DancespieleDB::temporary();
+
+        let mut paper_exchange = PaperExchange::new(&mut mock_exchange, dancespiele_db.clone());
+
+        paper_exchange.cancel_order("3344de344").unwrap();
+        paper_exchange
+            .add_order(stop_loss("KAVAEUR", "3.43", "1500"))
+            .unwrap();
+
+        let open_orders = paper_exchange.get_active_orders().unwrap().open;
+        let paper_orders = dancespiele_db.fetch_paper_orders().unwrap();
+
+        assert_eq!(open_orders.len(), 1);
+        assert_eq!(open_orders.get("PAPER-2").unwrap().price, "3.43");
+        assert_eq!(paper_orders[0].txid, "3344de344");
+        assert_eq!(paper_orders[0].status, "canceled");
+        assert_eq!(paper_orders[1].status, "open");
+        assert!(mock_exchange.placed_orders().is_empty());
+        assert!(mock_exchange.cancelled_orders().is_empty());
+        assert!(mock_exchange.open_orders().contains_key("3344de344"));
+    }
+
+    #[test]
+    fn should_trail_paper_stop_loss_with_real_prices() {
+        let mut mock_exchange = MockExchange::new()
+            .with_asset_pair("OXTEUR", "OXT", "ZEUR", 5, 8, "10")
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_prices("OXTEUR", &["0.40", "0.45"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("30.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("OXTEUR"),
+            ..Percentage::default()
+        }]);
+
+        let paper_exchange = PaperExchange::new(&mut mock_exchange, dancespiele_db.clone());
+        let mut worker =
+            Worker::new(paper_exchange, dancespiele_db.clone()).without_notifications();
+
+        worker.brain().unwrap();
+        worker.brain().unwrap();
+
+        let paper_orders = dancespiele_db.fetch_paper_orders().unwrap();
+
+        assert_eq!(paper_orders.len(), 2);
+        assert_eq!(paper_orders[0].status, "canceled");
+        assert_eq!(paper_orders[1].status, "open");
+        assert!(mock_exchange.placed_orders().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use super::types::{AssetPair, ClosedOrders, Ohlc, OpenOrders, OrderRequest, PriceSource, Trades};
+use super::Exchange;
+use crate::db::DancespieleDB;
+use crate::error::Result;
+use crate::kraken::stream;
+use std::collections::HashMap;
+
+/// Prices older than this are read from the exchange API again
+const MAX_PRICE_AGE: i64 = 60;
+
+/// Exchange reading the last trade and default close prices from the websocket feed. Pairs
+/// without a recent streamed price are read from the wrapped exchange and watched so the feed
+/// subscribes them, other price sources are always read from the wrapped exchange.
+/// The websocket names come from the asset pairs cached by the worker, never from the API.
+pub struct StreamingExchange<E: Exchange> {
+    exchange: E,
+    dancespiele_db: DancespieleDB,
+    asset_pairs: HashMap<String, AssetPair>,
+}
+
+impl<E: Exchange> StreamingExchange<E> {
+    pub fn new(exchange: E, dancespiele_db: DancespieleDB) -> Self {
+        Self {
+            exchange,
+            dancespiele_db,
+            asset_pairs: HashMap::new(),
+        }
+    }
+
+    fn cached_wsname(&mut self, pair: &str) -> Option<String> {
+        if self.asset_pairs.is_empty() {
+            self.asset_pairs = self
+                .dancespiele_db
+                .fetch_asset_pairs()
+                .ok()
+                .flatten()
+                .map(|cache| cache.asset_pairs)
+                .unwrap_or_default();
+        }
+
+        self.asset_pairs
+            .iter()
+            .find(|(name, asset_pair)| *name == pair || asset_pair.altname == pair)
+            .and_then(|(_name, asset_pair)| asset_pair.wsname.clone())
+    }
+}
+
+impl<E: Exchange> Exchange for StreamingExchange<E> {
+    fn get_current_balance(&mut self) -> Result<HashMap<String, String>> {
+        self.exchange.get_current_balance()
+    }
+
+    fn get_trades(&mut self, offset: u32) -> Result<Trades> {
+        self.exchange.get_trades(offset)
+    }
+
+    fn get_active_orders(&mut self) -> Result<OpenOrders> {
+        self.exchange.get_active_orders()
+    }
+
+    fn get_closed_orders(&mut self, start: i64, offset: u32) -> Result<ClosedOrders> {
+        self.exchange.get_closed_orders(start, offset)
+    }
+
+    fn get_price(&mut self, pair: &str, source: PriceSource) -> Result<String> {
+        if source != PriceSource::Last && source != PriceSource::default() {
+            return self.exchange.get_price(pair, source);
+        }
+
+        if let Some(price) = stream::last_price(pair, MAX_PRICE_AGE) {
+            return Ok(price.to_string());
+        }
+
+        if let Some(wsname) = self.cached_wsname(pair) {
+            stream::watch(&wsname, pair);
+        }
+
+        self.exchange.get_price(pair, source)
+    }
+
+    fn get_ohlc(&mut self, pair: &str, interval: u32) -> Result<Vec<Ohlc>> {
+        self.exchange.get_ohlc(pair, interval)
+    }
+
+    fn get_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>> {
+        self.exchange.get_asset_pairs()
+    }
+
+    fn add_order(&mut self, order: OrderRequest) -> Result<()> {
+        self.exchange.add_order(order)
+    }
+
+    fn cancel_order(&mut self, order: &str) -> Result<()> {
+        self.exchange.cancel_order(order)
+    }
+
+    fn validate_order(&mut self, order: OrderRequest) -> Result<()> {
+        self.exchange.validate_order(order)
+    }
+
+    fn edit_order(&mut self, txid: &str, order: OrderRequest) -> Result<bool> {
+        self.exchange.edit_order(txid, order)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.exchange.dry_run()
+    }
+
+    fn name(&self) -> String {
+        self.exchange.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamingExchange;
+    use crate::db::{AssetPairsCache, DancespieleDB};
+    use crate::exchange::mock::MockExchange;
+    use crate::exchange::types::PriceSource;
+    use crate::exchange::Exchange;
+    use crate::kraken::stream;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn should_read_streamed_price_and_fall_back_to_exchange() {
+        let mut mock_exchange = MockExchange::new()
+            .with_asset_pair("XOXTZEUR", "OXT", "ZEUR", 5, 8, "10")
+            .with_asset_pair("XSTRMZEUR", "STRM", "ZEUR", 5, 8, "10")
+            .with_prices("OXTEUR", &["0.40"])
+            .with_prices("STRMEUR", &["1.20"]);
+
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db
+            .save_asset_pairs(&AssetPairsCache {
+                time: 0,
+                asset_pairs: mock_exchange.get_asset_pairs().unwrap(),
+            })
+            .unwrap();
+
+        stream::set_last_price("STRMEUR", Decimal::new(125, 2));
+
+        let mut streaming_exchange = StreamingExchange::new(&mut mock_exchange, dancespiele_db);
+
+        assert_eq!(
+            streaming_exchange
+                .get_price("STRMEUR", PriceSource::Last)
+                .unwrap(),
+            "1.25"
+        );
+        assert_eq!(
+            streaming_exchange
+                .get_price("STRMEUR", PriceSource::Vwap(1))
+                .unwrap(),
+            "1.20"
+        );
+        assert_eq!(
+            streaming_exchange
+                .get_price("OXTEUR", PriceSource::default())
+                .unwrap(),
+            "0.40"
+        );
+        assert_eq!(streaming_exchange.name(), "mock");
+        assert_eq!(
+            streaming_exchange.cached_wsname("OXTEUR"),
+            Some(String::from("OXT/EUR"))
+        );
+    }
+}
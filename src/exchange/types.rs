@@ -0,0 +1,230 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[allow(clippy::upper_case_acronyms)]
+pub enum OperationType {
+    BUY,
+    SELL,
+}
+
+pub enum OrderType {
+    StopLoss,
+    StopLossLimit,
+    TakeProfit,
+    TakeProfitLimit,
+}
+
+/// Price the stops trail, the close of the latest one minute candle by default
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriceSource {
+    Last,
+    Bid,
+    Mid,
+    /// Volume weighted average price of the latest candle of the interval in minutes
+    Vwap(u32),
+    /// Close of the latest candle of the interval in minutes
+    Close(u32),
+}
+
+impl Default for PriceSource {
+    fn default() -> Self {
+        PriceSource::Close(1)
+    }
+}
+
+pub fn get_operation_type(operation_type: OperationType) -> String {
+    match operation_type {
+        OperationType::BUY => String::from("buy"),
+        OperationType::SELL => String::from("sell"),
+    }
+}
+
+pub fn get_order_type(order_type: OrderType) -> String {
+    match order_type {
+        OrderType::StopLoss => String::from("stop-loss"),
+        OrderType::StopLossLimit => String::from("stop-loss-limit"),
+        OrderType::TakeProfit => String::from("take-profit"),
+        OrderType::TakeProfitLimit => String::from("take-profit-limit"),
+    }
+}
+
+/// Trade of the account history, kept in sled to calculate the cost basis
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Trade {
+    pub pair: String,
+    #[serde(rename = "type")]
+    pub trade_type: String,
+    pub price: String,
+    #[serde(alias = "vol")]
+    pub volume: String,
+    pub fee: String,
+    pub time: f64,
+}
+
+/// Page of the trades history by trade id, `count` is the size of the whole history
+#[derive(Debug, Clone)]
+pub struct Trades {
+    pub count: u32,
+    pub trades: HashMap<String, Trade>,
+}
+
+/// Order of the account. `price` is the trigger price of stop and take profit orders and
+/// `average_price` the price it was executed at
+#[derive(Debug, Clone)]
+pub struct Order {
+    pub pair: String,
+    pub operation_type: String,
+    pub order_type: String,
+    pub price: String,
+    pub price2: Option<String>,
+    pub volume: String,
+    pub volume_executed: String,
+    pub average_price: String,
+    pub fee: String,
+    pub status: String,
+    pub userref: Option<i32>,
+    pub close_time: Option<f64>,
+}
+
+impl Order {
+    /// Volume still waiting to be executed
+    pub fn get_remaining_volume(&self) -> Decimal {
+        let volume = Decimal::from_str(&self.volume).unwrap_or(Decimal::ZERO);
+        let volume_executed = Decimal::from_str(&self.volume_executed).unwrap_or(Decimal::ZERO);
+
+        (volume - volume_executed).max(Decimal::ZERO).normalize()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenOrders {
+    pub open: HashMap<String, Order>,
+}
+
+/// Page of the closed orders by txid, `count` is the number of orders closed in the period
+#[derive(Debug, Clone, Default)]
+pub struct ClosedOrders {
+    pub closed: HashMap<String, Order>,
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderRequest {
+    pub pair: String,
+    pub operation_type: String,
+    pub order_type: String,
+    pub price: String,
+    pub price2: Option<String>,
+    pub volume: String,
+    pub userref: Option<i32>,
+}
+
+impl From<OrderRequest> for Order {
+    fn from(order: OrderRequest) -> Self {
+        Self {
+            pair: order.pair,
+            operation_type: order.operation_type,
+            order_type: order.order_type,
+            price: order.price,
+            price2: order.price2,
+            volume: order.volume,
+            volume_executed: String::from("0"),
+            average_price: String::from("0"),
+            fee: String::from("0"),
+            status: String::from("open"),
+            userref: order.userref,
+            close_time: None,
+        }
+    }
+}
+
+impl From<Order> for OrderRequest {
+    fn from(order: Order) -> Self {
+        let volume = order.get_remaining_volume();
+
+        Self {
+            pair: order.pair,
+            operation_type: order.operation_type,
+            order_type: order.order_type,
+            price: order.price,
+            price2: order.price2,
+            volume: volume.to_string(),
+            userref: order.userref,
+        }
+    }
+}
+
+/// Precision and limits of a pair, cached in sled
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssetPair {
+    pub altname: String,
+    pub base: String,
+    pub quote: String,
+    pub pair_decimals: u32,
+    pub lot_decimals: u32,
+    pub ordermin: Option<String>,
+    pub tick_size: Option<String>,
+    /// Name of the pair in the websocket API (`XBT/EUR`)
+    #[serde(default)]
+    pub wsname: Option<String>,
+}
+
+impl AssetPair {
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        let price = price.round_dp_with_strategy(self.pair_decimals, RoundingStrategy::ToZero);
+        let tick_size = self.get_tick_size();
+
+        if tick_size.is_zero() {
+            price.normalize()
+        } else {
+            ((price / tick_size).trunc() * tick_size).normalize()
+        }
+    }
+
+    /// Rounds up to the pair precision, for prices the order must not go below
+    pub fn round_price_up(&self, price: Decimal) -> Decimal {
+        let price =
+            price.round_dp_with_strategy(self.pair_decimals, RoundingStrategy::AwayFromZero);
+        let tick_size = self.get_tick_size();
+
+        if tick_size.is_zero() {
+            price.normalize()
+        } else {
+            ((price / tick_size).ceil() * tick_size).normalize()
+        }
+    }
+
+    pub fn round_volume(&self, volume: Decimal) -> Decimal {
+        volume
+            .round_dp_with_strategy(self.lot_decimals, RoundingStrategy::ToZero)
+            .normalize()
+    }
+
+    pub fn get_ordermin(&self) -> Decimal {
+        self.ordermin
+            .as_ref()
+            .and_then(|ordermin| Decimal::from_str(ordermin).ok())
+            .unwrap_or(Decimal::ZERO)
+    }
+
+    fn get_tick_size(&self) -> Decimal {
+        self.tick_size
+            .as_ref()
+            .and_then(|tick_size| Decimal::from_str(tick_size).ok())
+            .unwrap_or(Decimal::ZERO)
+    }
+}
+
+/// Candle of the OHLC data
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ohlc {
+    pub time: i64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub vwap: Decimal,
+    pub volume: Decimal,
+}
@@ -0,0 +1,172 @@
+use crate::dtos::{Action, TickReport};
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TickSummary {
+    pub tick_id: String,
+    pub time: i64,
+    pub dry_run: bool,
+    pub pairs: usize,
+    pub pairs_with_errors: usize,
+    pub placed: usize,
+    pub moved: usize,
+}
+
+impl From<&TickReport> for TickSummary {
+    fn from(report: &TickReport) -> Self {
+        let count = |action: Action| {
+            report
+                .pairs
+                .iter()
+                .filter(|pair| pair.action == action)
+                .count()
+        };
+
+        Self {
+            tick_id: report.tick_id.clone(),
+            time: report.time,
+            dry_run: report.dry_run,
+            pairs: report.pairs.len(),
+            pairs_with_errors: report
+                .pairs
+                .iter()
+                .filter(|pair| pair.error.is_some())
+                .count(),
+            placed: count(Action::Placed),
+            moved: count(Action::Moved),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Checks {
+    pub last_tick: bool,
+    pub database: bool,
+    pub credentials: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Checks,
+    pub last_success: Option<i64>,
+    pub last_tick: Option<TickSummary>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Seconds since the last successful tick after which the worker is not ready
+    max_age: i64,
+    database: bool,
+    credentials: bool,
+    last_success: Option<i64>,
+    last_tick: Option<TickSummary>,
+}
+
+impl State {
+    fn readiness(&self, now: i64) -> Readiness {
+        let checks = Checks {
+            last_tick: self
+                .last_success
+                .map(|last_success| now - last_success <= self.max_age)
+                .unwrap_or(false),
+            database: self.database,
+            credentials: self.credentials,
+        };
+
+        Readiness {
+            ready: checks.last_tick && checks.database && checks.credentials,
+            checks,
+            last_success: self.last_success,
+            last_tick: self.last_tick.clone(),
+        }
+    }
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    max_age: 0,
+    database: false,
+    credentials: false,
+    last_success: None,
+    last_tick: None,
+});
+
+fn update(update: impl FnOnce(&mut State)) {
+    if let Ok(mut state) = STATE.lock() {
+        update(&mut state);
+    }
+}
+
+/// The worker is ready while a tick succeeded in the last `intervals` runs of the schedule
+pub fn configure(interval_secs: i64, intervals: i64) {
+    update(|state| state.max_age = interval_secs * intervals);
+}
+
+pub fn set_database(open: bool) {
+    update(|state| state.database = open);
+}
+
+pub fn set_credentials(loaded: bool) {
+    update(|state| state.credentials = loaded);
+}
+
+pub fn record_tick(report: &TickReport) {
+    update(|state| {
+        state.last_success = Some(Utc::now().timestamp());
+        state.last_tick = Some(TickSummary::from(report));
+    });
+}
+
+pub fn readiness() -> Readiness {
+    match STATE.lock() {
+        Ok(state) => state.readiness(Utc::now().timestamp()),
+        Err(_) => State::default().readiness(Utc::now().timestamp()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{State, TickSummary};
+    use crate::dtos::TickReport;
+
+    fn get_state(last_success: Option<i64>) -> State {
+        State {
+            max_age: 360,
+            database: true,
+            credentials: true,
+            last_success,
+            last_tick: Some(TickSummary::from(&TickReport::from((
+                String::from("1600000000000"),
+                1600000000,
+                false,
+                vec![],
+            )))),
+        }
+    }
+
+    #[test]
+    fn should_be_ready_after_recent_tick() {
+        let readiness = get_state(Some(1600000000)).readiness(1600000300);
+
+        assert!(readiness.ready);
+        assert_eq!(readiness.last_tick.unwrap().tick_id, "1600000000000");
+    }
+
+    #[test]
+    fn should_not_be_ready_without_recent_tick_or_dependencies() {
+        assert!(!get_state(Some(1600000000)).readiness(1600000400).ready);
+        assert!(!get_state(None).readiness(1600000000).ready);
+
+        let readiness = State {
+            database: false,
+            ..get_state(Some(1600000000))
+        }
+        .readiness(1600000300);
+
+        assert!(!readiness.ready);
+        assert!(readiness.checks.last_tick);
+        assert!(!readiness.checks.database);
+    }
+}
@@ -1,5 +1,8 @@
+use crate::exchange::types;
+use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Trade {
@@ -55,9 +58,12 @@ pub struct Order {
     pub status: String,
     #[serde(rename = "stopprice")]
     pub stop_price: String,
-    pub userref: u32,
+    #[serde(default)]
+    pub userref: Option<i32>,
     pub vol: String,
     pub vol_exec: String,
+    #[serde(default)]
+    pub closetm: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -65,98 +71,110 @@ pub struct OpenOrders {
     pub open: HashMap<String, Order>,
 }
 
-#[derive(Clone, Debug)]
-pub struct FutureOperation {
-    pub buy_price: f32,
-    pub pair: String,
-    pub quantity: String,
-    pub operation_time: i64,
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClosedOrders {
+    pub closed: HashMap<String, Order>,
+    pub count: u32,
 }
 
-impl From<(Trade, String)> for FutureOperation {
-    fn from(future_operation: (Trade, String)) -> Self {
-        let (trade, quantity) = future_operation;
-
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssetPair {
+    pub altname: String,
+    pub base: String,
+    pub quote: String,
+    pub pair_decimals: u32,
+    pub lot_decimals: u32,
+    pub ordermin: Option<String>,
+    pub tick_size: Option<String>,
+    #[serde(default)]
+    pub wsname: Option<String>,
+}
+
+impl From<Trade> for types::Trade {
+    fn from(trade: Trade) -> Self {
         Self {
-            buy_price: trade.price.parse().unwrap(),
             pair: trade.pair,
-            quantity,
-            operation_time: trade.time as i64,
+            trade_type: trade.trade_type,
+            price: trade.price,
+            volume: trade.vol,
+            fee: trade.fee,
+            time: trade.time,
         }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct CurrentPrice {
-    pub pair: String,
-    pub price: f32,
-}
-
-impl From<(String, f32)> for CurrentPrice {
-    fn from(current_price: (String, f32)) -> Self {
-        let (pair, price) = current_price;
-
-        Self { pair, price }
+impl From<Trades> for types::Trades {
+    fn from(trades: Trades) -> Self {
+        Self {
+            count: trades.count,
+            trades: trades
+                .trades
+                .into_iter()
+                .map(|(id, trade)| (id, types::Trade::from(trade)))
+                .collect(),
+        }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct StopLossActive {
-    pub order: String,
-    pub price: f32,
-    pub pair: String,
-    pub current_price: f32,
-}
-
-impl From<(String, f32, CurrentPrice)> for StopLossActive {
-    fn from(stop_loss_active: (String, f32, CurrentPrice)) -> Self {
-        let (order, price, current_price) = stop_loss_active;
-
+impl From<Order> for types::Order {
+    fn from(order: Order) -> Self {
         Self {
-            order,
-            price,
-            pair: current_price.pair,
-            current_price: current_price.price,
+            pair: order.description.pair,
+            operation_type: order.description.operation_type,
+            order_type: order.description.order_type,
+            // The trigger price is in `descr`, Kraken reports the average fill price in `price`
+            price: order.description.price,
+            price2: Some(order.description.price2).filter(|price2| {
+                Decimal::from_str(price2)
+                    .map(|price2| !price2.is_zero())
+                    .unwrap_or(false)
+            }),
+            volume: order.vol,
+            volume_executed: order.vol_exec,
+            average_price: order.price,
+            fee: order.fee,
+            status: order.status,
+            userref: order.userref,
+            close_time: order.closetm,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Notify {
-    pub pair: String,
-    pub price: String,
-    pub benefit: String,
+fn into_orders(orders: HashMap<String, Order>) -> HashMap<String, types::Order> {
+    orders
+        .into_iter()
+        .map(|(txid, order)| (txid, types::Order::from(order)))
+        .collect()
 }
 
-impl From<(String, String, String)> for Notify {
-    fn from(notify: (String, String, String)) -> Self {
-        let (pair, price, benefit) = notify;
-
+impl From<OpenOrders> for types::OpenOrders {
+    fn from(orders: OpenOrders) -> Self {
         Self {
-            pair,
-            price,
-            benefit,
+            open: into_orders(orders.open),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct NotifyEmail {
-    pub pair: String,
-    pub price: String,
-    pub benefit: String,
-    pub email: String,
+impl From<ClosedOrders> for types::ClosedOrders {
+    fn from(orders: ClosedOrders) -> Self {
+        Self {
+            closed: into_orders(orders.closed),
+            count: orders.count,
+        }
+    }
 }
 
-impl From<(Notify, String)> for NotifyEmail {
-    fn from(notify: (Notify, String)) -> Self {
-        let (content, email) = notify;
-
+impl From<AssetPair> for types::AssetPair {
+    fn from(asset_pair: AssetPair) -> Self {
         Self {
-            price: content.price,
-            pair: content.pair,
-            benefit: content.benefit,
-            email,
+            altname: asset_pair.altname,
+            base: asset_pair.base,
+            quote: asset_pair.quote,
+            pair_decimals: asset_pair.pair_decimals,
+            lot_decimals: asset_pair.lot_decimals,
+            ordermin: asset_pair.ordermin,
+            tick_size: asset_pair.tick_size,
+            wsname: asset_pair.wsname,
         }
     }
 }
@@ -170,25 +188,26 @@ pub struct Claims {
     pub exp: i64,
 }
 
-#[derive(Clone, Debug)]
-pub struct Info {
-    pub pair: String,
-    pub current_price: f32,
-    pub price_bought: f32,
-    pub benefit: String,
-    pub current_stop_loss: String,
-}
+#[cfg(test)]
+mod tests {
+    use super::OpenOrders;
+    use crate::exchange::types;
 
-impl From<(CurrentPrice, f32, String, String)> for Info {
-    fn from(info: (CurrentPrice, f32, String, String)) -> Self {
-        let (current_price, price_bought, benefit, current_stop_loss) = info;
+    #[test]
+    fn should_read_trigger_price_of_open_order_from_description() {
+        let open_orders: OpenOrders = serde_json::from_str(
+            r#"{"open":{"OQCLML-BW3P3-BUCMWZ":{"refid":null,"userref":7,"status":"open","opentm":1616665496.7808,"starttm":0,"expiretm":0,"descr":{"pair":"OXTEUR","type":"sell","ordertype":"stop-loss-limit","price":"0.392","price2":"0.388","leverage":"none","order":"sell 4000.00000000 OXTEUR @ stop loss 0.392 -> limit 0.388","close":""},"vol":"4000.00000000","vol_exec":"1000.00000000","cost":"0.00000","fee":"0.00000","price":"0.00000","stopprice":"0.00000","limitprice":"0.00000","misc":"","oflags":"fciq"}}}"#,
+        )
+        .unwrap();
 
-        Self {
-            pair: current_price.pair,
-            current_price: current_price.price,
-            price_bought,
-            benefit,
-            current_stop_loss,
-        }
+        let open_orders = types::OpenOrders::from(open_orders);
+        let order = open_orders.open.get("OQCLML-BW3P3-BUCMWZ").unwrap();
+
+        assert_eq!(order.price, "0.392");
+        assert_eq!(order.price2, Some(String::from("0.388")));
+        assert_eq!(order.average_price, "0.00000");
+        assert_eq!(order.order_type, "stop-loss-limit");
+        assert_eq!(order.userref, Some(7));
+        assert_eq!(order.get_remaining_volume().to_string(), "3000");
     }
 }
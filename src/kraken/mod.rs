@@ -1,5 +1,7 @@
 pub mod dtos;
-mod helpers;
 mod operations;
+mod private;
+pub mod stream;
 
 pub use operations::KrakenOpr;
+pub use private::PrivateApi;
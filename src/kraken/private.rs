@@ -0,0 +1,246 @@
+use crate::exchange::types::OrderRequest;
+use chrono::Utc;
+use coinnect::error::{Error, ErrorKind, Result};
+use curl::easy::{Easy, List};
+use openssl::base64;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign::Signer;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+const KRAKEN_API_URL: &str = "https://api.kraken.com";
+
+#[derive(Deserialize)]
+struct KrakenKeys {
+    api_key: String,
+    api_secret: String,
+}
+
+/// Private Kraken endpoints coinnect does not expose, signed with the keys of `keys.json`
+pub struct PrivateApi {
+    api_key: String,
+    api_secret: Vec<u8>,
+    last_nonce: i64,
+}
+
+impl PrivateApi {
+    pub fn new_from_file(name: &str, path: PathBuf) -> Result<Self> {
+        let keys_string = fs::read_to_string(path).map_err(|err| Error::from(err.to_string()))?;
+        let mut keys: HashMap<String, KrakenKeys> = serde_json::from_str(&keys_string)?;
+        let keys = keys
+            .remove(name)
+            .ok_or_else(|| Error::from_kind(ErrorKind::MissingField(name.to_string())))?;
+
+        Ok(Self {
+            api_key: keys.api_key,
+            api_secret: base64::decode_block(&keys.api_secret)
+                .map_err(|_| Error::from_kind(ErrorKind::BadCredentials))?,
+            last_nonce: 0,
+        })
+    }
+
+    /// Amends the price and volume of an open order, Kraken gives the edited order a new txid
+    pub fn edit_order(&mut self, txid: &str, order: &OrderRequest) -> Result<bool> {
+        let userref = order.userref.map(|userref| userref.to_string());
+        let mut params = vec![
+            ("txid", txid),
+            ("pair", order.pair.as_str()),
+            ("volume", order.volume.as_str()),
+            ("price", order.price.as_str()),
+        ];
+
+        if let Some(price2) = &order.price2 {
+            params.push(("price2", price2));
+        }
+
+        if let Some(userref) = &userref {
+            params.push(("userref", userref));
+        }
+
+        parse_edit_order(&self.private_query("EditOrder", &params)?)
+    }
+
+    fn private_query(
+        &mut self,
+        method: &str,
+        params: &[(&str, &str)],
+    ) -> Result<Map<String, Value>> {
+        let path = format!("/0/private/{}", method);
+        // Milliseconds like coinnect, so the nonces of both clients keep increasing
+        let nonce = Utc::now().timestamp_millis().max(self.last_nonce + 1);
+        let mut easy = Easy::new();
+
+        self.last_nonce = nonce;
+
+        let nonce = nonce.to_string();
+        let post_data = std::iter::once(("nonce", nonce.as_str()))
+            .chain(params.iter().cloned())
+            .map(|(key, value)| format!("{}={}", key, easy.url_encode(value.as_bytes())))
+            .collect::<Vec<String>>()
+            .join("&");
+        let signature = sign(&path, &nonce, &post_data, &self.api_secret)?;
+
+        let mut headers = List::new();
+        headers
+            .append(&format!("API-Key: {}", self.api_key))
+            .map_err(curl_error)?;
+        headers
+            .append(&format!("API-Sign: {}", signature))
+            .map_err(curl_error)?;
+
+        easy.url(&format!("{}{}", KRAKEN_API_URL, path))
+            .map_err(curl_error)?;
+        easy.http_headers(headers).map_err(curl_error)?;
+        easy.post_fields_copy(post_data.as_bytes())
+            .map_err(curl_error)?;
+
+        let mut body = Vec::new();
+        {
+            let mut transfer = easy.transfer();
+
+            transfer
+                .write_function(|data| {
+                    body.extend_from_slice(data);
+                    Ok(data.len())
+                })
+                .map_err(curl_error)?;
+            transfer.perform().map_err(curl_error)?;
+        }
+
+        parse_response(&String::from_utf8_lossy(&body))
+    }
+}
+
+/// `API-Sign` header, the HMAC-SHA512 of the path and the SHA256 of the nonce and the post data
+fn sign(path: &str, nonce: &str, post_data: &str, api_secret: &[u8]) -> Result<String> {
+    let key = PKey::hmac(api_secret).map_err(openssl_error)?;
+    let mut signer = Signer::new(MessageDigest::sha512(), &key).map_err(openssl_error)?;
+
+    signer.update(path.as_bytes()).map_err(openssl_error)?;
+    signer
+        .update(&sha256(format!("{}{}", nonce, post_data).as_bytes()))
+        .map_err(openssl_error)?;
+
+    Ok(base64::encode_block(
+        &signer.sign_to_vec().map_err(openssl_error)?,
+    ))
+}
+
+/// Kraken answers `{"error": [...], "result": {...}}`, the errors are mapped like coinnect does
+fn parse_response(body: &str) -> Result<Map<String, Value>> {
+    let response: Map<String, Value> = serde_json::from_str(body)?;
+    let errors: Vec<String> = response
+        .get("error")
+        .and_then(|errors| errors.as_array())
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|error| error.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Some(error) = errors.first() {
+        return Err(Error::from_kind(match error.as_str() {
+            "EAPI:Invalid key" | "EAPI:Invalid signature" => ErrorKind::BadCredentials,
+            "EAPI:Invalid nonce" => ErrorKind::InvalidNonce,
+            "EAPI:Rate limit exceeded" | "EOrder:Rate limit exceeded" => {
+                ErrorKind::RateLimitExceeded
+            }
+            "EGeneral:Permission denied" => ErrorKind::PermissionDenied,
+            "EGeneral:Invalid arguments" => ErrorKind::InvalidArguments,
+            "EService:Unavailable" | "EService:Busy" => {
+                ErrorKind::ServiceUnavailable(error.to_string())
+            }
+            _ => ErrorKind::ExchangeSpecificError(errors.join(", ")),
+        }));
+    }
+
+    Ok(response)
+}
+
+fn parse_edit_order(response: &Map<String, Value>) -> Result<bool> {
+    let result = response
+        .get("result")
+        .ok_or_else(|| Error::from_kind(ErrorKind::MissingField("result".to_string())))?;
+
+    match result.get("status").and_then(|status| status.as_str()) {
+        Some("ok") => Ok(true),
+        Some(_) => Err(Error::from_kind(ErrorKind::ExchangeSpecificError(
+            result
+                .get("error_message")
+                .and_then(|message| message.as_str())
+                .unwrap_or("order could not be edited")
+                .to_string(),
+        ))),
+        None => Err(Error::from_kind(ErrorKind::MissingField(
+            "status".to_string(),
+        ))),
+    }
+}
+
+fn curl_error(err: curl::Error) -> Error {
+    Error::from_kind(ErrorKind::ServiceUnavailable(err.to_string()))
+}
+
+fn openssl_error(err: openssl::error::ErrorStack) -> Error {
+    Error::from(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_edit_order, parse_response, sign};
+    use coinnect::error::ErrorKind;
+    use openssl::base64;
+
+    #[test]
+    fn should_sign_private_request() {
+        let api_secret = base64::decode_block(
+            "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==",
+        )
+        .unwrap();
+
+        assert_eq!(
+            sign(
+                "/0/private/AddOrder",
+                "1616492376594",
+                "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25",
+                &api_secret,
+            )
+            .unwrap(),
+            "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ=="
+        );
+    }
+
+    #[test]
+    fn should_parse_edit_order_response() {
+        let edited = parse_response(
+            r#"{"error":[],"result":{"status":"ok","txid":"OFVXHJ-KPQ3B-VS7ELA","originaltxid":"OHYO67-6LP66-HMQ437","volume":"4000","price":"0.392","orders_cancelled":1,"descr":{"order":"sell 4000 OXTEUR @ stop loss 0.392"}}}"#,
+        )
+        .unwrap();
+        let failed = parse_response(
+            r#"{"error":[],"result":{"status":"err","error_message":"Order not found"}}"#,
+        )
+        .unwrap();
+
+        assert!(parse_edit_order(&edited).unwrap());
+        assert!(parse_edit_order(&failed).is_err());
+        assert!(matches!(
+            parse_response(r#"{"error":["EAPI:Rate limit exceeded"]}"#)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::RateLimitExceeded
+        ));
+        assert!(matches!(
+            parse_response(r#"{"error":["EOrder:Unknown order"]}"#)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ExchangeSpecificError(_)
+        ));
+    }
+}
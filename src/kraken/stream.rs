@@ -0,0 +1,242 @@
+use crate::error::Result;
+use crate::logger;
+use chrono::Utc;
+use rust_decimal::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{connect, Message, WebSocket};
+
+pub const KRAKEN_WS_URL: &str = "wss://ws.kraken.com";
+
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Last streamed price and the time it arrived by pair (altname)
+static LAST_PRICES: Mutex<BTreeMap<String, (Decimal, i64)>> = Mutex::new(BTreeMap::new());
+/// Pairs the feed has to subscribe by their websocket name (`XBT/EUR`)
+static WATCHED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+pub fn last_price(pair: &str, max_age: i64) -> Option<Decimal> {
+    let last_prices = LAST_PRICES.lock().ok()?;
+    let (price, time) = last_prices.get(pair)?;
+
+    if Utc::now().timestamp() - time <= max_age {
+        Some(*price)
+    } else {
+        None
+    }
+}
+
+pub fn set_last_price(pair: &str, price: Decimal) {
+    if let Ok(mut last_prices) = LAST_PRICES.lock() {
+        last_prices.insert(pair.to_string(), (price, Utc::now().timestamp()));
+    }
+}
+
+/// Adds the pair to the ones the feed streams, it is subscribed in the next read of the feed
+pub fn watch(wsname: &str, pair: &str) {
+    if let Ok(mut watched) = WATCHED.lock() {
+        watched.insert(wsname.to_string(), pair.to_string());
+    }
+}
+
+fn watched() -> BTreeMap<String, String> {
+    WATCHED
+        .lock()
+        .map(|watched| watched.clone())
+        .unwrap_or_default()
+}
+
+pub fn subscribe_message(wsnames: &[String], channel: &str) -> String {
+    json!({
+        "event": "subscribe",
+        "pair": wsnames,
+        "subscription": { "name": channel },
+    })
+    .to_string()
+}
+
+/// Price of a ticker (last trade closed) or trade (last trade of the batch) message
+pub fn parse_message(message: &str) -> Option<(String, Decimal)> {
+    let value: Value = serde_json::from_str(message).ok()?;
+    let fields = value.as_array()?;
+
+    if fields.len() < 4 {
+        return None;
+    }
+
+    let wsname = fields[fields.len() - 1].as_str()?;
+    let channel = fields[fields.len() - 2].as_str()?;
+
+    let price = match channel {
+        "ticker" => fields[1].get("c")?.get(0)?.as_str()?,
+        "trade" => fields[1].as_array()?.last()?.get(0)?.as_str()?,
+        _ => return None,
+    };
+
+    Some((wsname.to_string(), Decimal::from_str(price).ok()?))
+}
+
+pub struct PriceFeed {
+    url: String,
+    channel: String,
+    events: Sender<()>,
+}
+
+impl PriceFeed {
+    /// Every streamed price is announced through `events` so the worker can evaluate the stops
+    pub fn new(url: &str, channel: &str, events: Sender<()>) -> Self {
+        Self {
+            url: url.to_string(),
+            channel: channel.to_string(),
+            events,
+        }
+    }
+
+    /// Keeps the feed connected, reconnecting after errors
+    pub fn run(self) {
+        loop {
+            match self.session() {
+                Ok(()) => logger::warn("Price stream closed").emit(),
+                Err(err) => logger::error("Price stream failed")
+                    .field("error", err.to_string())
+                    .emit(),
+            }
+
+            thread::sleep(RECONNECT_DELAY);
+        }
+    }
+
+    fn session(&self) -> Result<()> {
+        let (mut socket, _response) = connect(self.url.as_str())?;
+        let mut subscribed: HashSet<String> = HashSet::new();
+
+        set_read_timeout(&socket)?;
+
+        logger::info("Price stream connected")
+            .field("url", &self.url)
+            .field("channel", &self.channel)
+            .emit();
+
+        loop {
+            let watched = watched();
+            let pending: Vec<String> = watched
+                .keys()
+                .filter(|wsname| !subscribed.contains(*wsname))
+                .cloned()
+                .collect();
+
+            if !pending.is_empty() {
+                socket.write_message(Message::Text(subscribe_message(&pending, &self.channel)))?;
+                subscribed.extend(pending);
+            }
+
+            match socket.read_message() {
+                Ok(Message::Text(message)) => {
+                    if let Some((pair, price)) = parse_message(&message)
+                        .and_then(|(wsname, price)| Some((watched.get(&wsname)?.clone(), price)))
+                    {
+                        set_last_price(&pair, price);
+                        self.events.send(()).ok();
+                    }
+                }
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(err))
+                    if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut =>
+                {
+                    continue
+                }
+                Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Reads return periodically so new watched pairs are subscribed without waiting for a message
+fn set_read_timeout(socket: &WebSocket<MaybeTlsStream<TcpStream>>) -> std::io::Result<()> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(READ_TIMEOUT))?,
+        MaybeTlsStream::NativeTls(stream) => {
+            stream.get_ref().set_read_timeout(Some(READ_TIMEOUT))?
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{last_price, parse_message, watch, PriceFeed};
+    use rust_decimal::Decimal;
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use tungstenite::{accept, Message};
+
+    const TICKER: &str = r#"[340,{"a":["3.52000",1,"1.000"],"b":["3.50000",20,"20.000"],"c":["3.51000","12.5"],"v":["100","200"],"p":["3.4","3.3"],"t":[10,20],"l":["3.1","3.0"],"h":["3.6","3.6"],"o":["3.2","3.1"]},"ticker","KAVA/EUR"]"#;
+
+    #[test]
+    fn should_parse_ticker_and_trade_messages() {
+        let trade = r#"[321,[["0.40100","10.0","1616663618.1","s","l",""],["0.40200","5.0","1616663619.2","b","m",""]],"trade","OXT/EUR"]"#;
+
+        assert_eq!(
+            parse_message(TICKER),
+            Some((String::from("KAVA/EUR"), Decimal::from_str("3.51").unwrap()))
+        );
+        assert_eq!(
+            parse_message(trade),
+            Some((String::from("OXT/EUR"), Decimal::from_str("0.402").unwrap()))
+        );
+        assert_eq!(parse_message(r#"{"event":"heartbeat"}"#), None);
+    }
+
+    #[test]
+    fn should_stream_prices_of_watched_pairs() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut socket = accept(listener.accept().unwrap().0).unwrap();
+            let subscription = socket.read_message().unwrap().into_text().unwrap();
+
+            socket
+                .write_message(Message::Text(String::from(
+                    r#"{"event":"subscriptionStatus","status":"subscribed"}"#,
+                )))
+                .unwrap();
+            socket
+                .write_message(Message::Text(TICKER.to_string()))
+                .unwrap();
+            socket.close(None).unwrap();
+            while socket.read_message().is_ok() {}
+
+            subscription
+        });
+
+        let (sender, receiver) = channel();
+        watch("KAVA/EUR", "KAVAEUR");
+
+        PriceFeed::new(&url, "ticker", sender).session().unwrap();
+
+        let subscription = server.join().unwrap();
+
+        assert!(subscription.contains("\"KAVA/EUR\""));
+        assert!(subscription.contains("\"name\":\"ticker\""));
+        assert_eq!(
+            last_price("KAVAEUR", 60),
+            Some(Decimal::from_str("3.51").unwrap())
+        );
+        assert!(receiver.try_recv().is_ok());
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.to_lowercase().as_str() {
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(format!("{} is not a log level", level)),
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static CONTEXT: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
+
+pub fn init(json: bool, level: Level) {
+    JSON.store(json, Ordering::Relaxed);
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Fields added to every event until the next tick starts
+pub fn set_context(tick_id: &str, exchange: &str) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        *context = vec![
+            (String::from("tick_id"), tick_id.to_string()),
+            (String::from("exchange"), exchange.to_string()),
+        ];
+    }
+}
+
+pub struct Event {
+    level: Level,
+    message: String,
+    fields: Map<String, Value>,
+}
+
+impl Event {
+    pub fn pair(self, pair: &str) -> Self {
+        self.field("pair", pair)
+    }
+
+    pub fn order(self, order: &str) -> Self {
+        self.field("order_id", order)
+    }
+
+    pub fn field<V: Serialize>(mut self, key: &str, value: V) -> Self {
+        self.fields.insert(
+            key.to_string(),
+            serde_json::to_value(value).unwrap_or(Value::Null),
+        );
+        self
+    }
+
+    fn format(&self, json: bool) -> String {
+        let time = Utc::now().to_rfc3339();
+
+        if json {
+            let mut line = Map::new();
+            line.insert(String::from("time"), Value::from(time));
+            line.insert(String::from("level"), Value::from(self.level.as_str()));
+            line.insert(String::from("message"), Value::from(self.message.clone()));
+            line.extend(self.fields.clone());
+
+            Value::Object(line).to_string()
+        } else {
+            self.fields.iter().fold(
+                format!(
+                    "{} {} {}",
+                    time,
+                    self.level.as_str().to_uppercase(),
+                    self.message
+                ),
+                |line, (key, value)| match value {
+                    Value::String(value) => format!("{} {}={}", line, key, value),
+                    value => format!("{} {}={}", line, key, value),
+                },
+            )
+        }
+    }
+
+    pub fn emit(mut self) {
+        if (self.level as u8) < LEVEL.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if let Ok(context) = CONTEXT.lock() {
+            context.iter().for_each(|(key, value)| {
+                self.fields
+                    .entry(key.clone())
+                    .or_insert_with(|| Value::from(value.clone()));
+            });
+        }
+
+        let line = self.format(JSON.load(Ordering::Relaxed));
+
+        if self.level >= Level::Warn {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+fn event(level: Level, message: &str) -> Event {
+    Event {
+        level,
+        message: message.to_string(),
+        fields: Map::new(),
+    }
+}
+
+pub fn debug(message: &str) -> Event {
+    event(Level::Debug, message)
+}
+
+pub fn info(message: &str) -> Event {
+    event(Level::Info, message)
+}
+
+pub fn warn(message: &str) -> Event {
+    event(Level::Warn, message)
+}
+
+pub fn error(message: &str) -> Event {
+    event(Level::Error, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error, Level};
+    use serde_json::Value;
+    use std::str::FromStr;
+
+    #[test]
+    fn should_format_json_lines_with_fields() {
+        let line = error("Stop loss could not be placed")
+            .pair("KAVAEUR")
+            .order("OMOCK-1")
+            .field("price", "3.43")
+            .format(true);
+
+        let value: Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(value["level"], "error");
+        assert_eq!(value["message"], "Stop loss could not be placed");
+        assert_eq!(value["pair"], "KAVAEUR");
+        assert_eq!(value["order_id"], "OMOCK-1");
+        assert_eq!(value["price"], "3.43");
+    }
+
+    #[test]
+    fn should_format_text_lines_with_fields() {
+        let line = error("Stop loss could not be placed")
+            .pair("KAVAEUR")
+            .format(false);
+
+        assert!(line.ends_with("ERROR Stop loss could not be placed pair=KAVAEUR"));
+        assert_eq!(Level::from_str("WARN"), Ok(Level::Warn));
+        assert!(Level::from_str("verbose").is_err());
+    }
+}
@@ -1,50 +1,225 @@
+mod cost_basis;
 mod db;
+pub mod dtos;
+mod error;
+mod exchange;
+mod health;
 pub mod kraken;
+mod logger;
+mod metrics;
+mod scheduler;
+mod server;
 mod services;
 pub mod utils;
+mod volatility;
+mod worker;
 
 use coinnect::kraken::KrakenCreds;
-use cronjob::CronJob;
+use db::DancespieleDB;
 use dotenv::dotenv;
-use kraken::KrakenOpr;
+use dtos::TickReport;
+use error::{Error, Result};
+use exchange::{Exchange, PaperExchange, StreamingExchange};
+use kraken::stream::{PriceFeed, KRAKEN_WS_URL};
+use kraken::{KrakenOpr, PrivateApi};
+use logger::Level;
+use rust_decimal::Decimal;
+use scheduler::{Schedule, Scheduler};
 use std::env;
 use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+use worker::Worker;
 
 fn main() {
     dotenv().ok();
 
-    let multiples = get_multiples(2);
-    // Create the `CronJob` object.
-    let mut cron = CronJob::new("Dancespiele", on_cron);
-    cron.seconds("0");
-    cron.minutes(&multiples);
-    cron.start_job();
+    init_logger();
+
+    let mut scheduler = get_scheduler().unwrap_or_else(|err| {
+        logger::error("Schedule could not be configured")
+            .field("error", err.to_string())
+            .emit();
+        process::exit(1);
+    });
+    let ready_intervals = env::var("READY_INTERVALS")
+        .ok()
+        .and_then(|intervals| intervals.parse().ok())
+        .unwrap_or(3);
+
+    health::configure(scheduler.interval().as_secs() as i64, ready_intervals);
+
+    if let Ok(http_addr) = env::var("HTTP_ADDR") {
+        if let Err(err) = server::serve(&http_addr) {
+            logger::error("HTTP server could not be started")
+                .field("addr", &http_addr)
+                .field("error", err.to_string())
+                .emit();
+        }
+    }
+
+    if is_streaming() {
+        let (sender, receiver) = channel();
+        let url = env::var("STREAM_URL").unwrap_or_else(|_| String::from(KRAKEN_WS_URL));
+        let channel_name = env::var("STREAM_CHANNEL").unwrap_or_else(|_| String::from("ticker"));
+        let min_gap = env::var("STREAM_MIN_GAP")
+            .ok()
+            .and_then(|min_gap| min_gap.parse().ok())
+            .unwrap_or(30);
+
+        thread::spawn(move || PriceFeed::new(&url, &channel_name, sender).run());
+
+        scheduler = scheduler.with_wakeups(receiver, Duration::from_secs(min_gap));
+    }
+
+    scheduler.start(on_tick);
+}
+
+fn is_streaming() -> bool {
+    env::var("PRICE_STREAM")
+        .map(|price_stream| price_stream == "true")
+        .unwrap_or(false)
 }
 
-// Our cronjob handler.
-fn on_cron(_name: &str) {
-    let sled_url = env::var("SLED_URL").expect("SLED_URL must be set");
+fn get_scheduler() -> Result<Scheduler> {
+    let schedule = match env::var("SCHEDULE") {
+        Ok(schedule) => Schedule::from_str(&schedule)?,
+        Err(_) => Scheduler::default_schedule(),
+    };
+    let jitter = match env::var("SCHEDULE_JITTER") {
+        Ok(jitter) => jitter
+            .parse()
+            .map_err(|_| Error::Config(String::from("SCHEDULE_JITTER must be seconds")))?,
+        Err(_) => 0,
+    };
+
+    Ok(Scheduler::new(schedule).with_jitter(Duration::from_secs(jitter)))
+}
+
+fn on_tick() {
+    let started = Instant::now();
+    let result = run();
+
+    metrics::observe_tick(result.as_ref().ok(), started.elapsed());
+
+    if let Ok(report) = &result {
+        health::record_tick(report);
+    }
+
+    match result {
+        Ok(report) => logger::info("Tick finished")
+            .field("tick_id", &report.tick_id)
+            .field("report", &report)
+            .emit(),
+        Err(err) => {
+            metrics::record_error(&err);
+            logger::error("Tick failed")
+                .field("error", err.to_string())
+                .emit()
+        }
+    }
+}
+
+fn init_logger() {
+    let json = env::var("LOG_FORMAT")
+        .map(|log_format| log_format == "json")
+        .unwrap_or(false);
+    let level = env::var("LOG_LEVEL")
+        .ok()
+        .and_then(|level| Level::from_str(&level).ok())
+        .unwrap_or(Level::Info);
+
+    logger::init(json, level);
+}
+
+fn run() -> Result<TickReport> {
+    let sled_url =
+        env::var("SLED_URL").map_err(|_| Error::Config(String::from("SLED_URL must be set")))?;
     let trading_agreement = if let Ok(trading_agreement) = env::var("TRADING_AGREEMENT") {
         trading_agreement
     } else {
         String::from("")
     };
 
-    let cred =
-        KrakenCreds::new_from_file("account_kraken", Path::new("keys.json").to_path_buf()).unwrap();
+    let keys_path = Path::new("keys.json").to_path_buf();
+    let cred = KrakenCreds::new_from_file("account_kraken", keys_path.clone()).and_then(|cred| {
+        Ok((
+            cred,
+            PrivateApi::new_from_file("account_kraken", keys_path)?,
+        ))
+    });
+
+    health::set_credentials(cred.is_ok());
+
+    let (cred, private_api) =
+        cred.map_err(|err| Error::Config(format!("keys.json could not be read: {}", err)))?;
+
+    let dry_run = env::var("DRY_RUN")
+        .map(|dry_run| dry_run == "true")
+        .unwrap_or(false);
+
+    let kraken_opr = KrakenOpr::new(cred, private_api, trading_agreement)?;
+    let dancespiele_db = DancespieleDB::new(&sled_url);
+
+    health::set_database(dancespiele_db.is_ok());
 
-    let mut kraken_opr = KrakenOpr::new(cred, &sled_url, trading_agreement);
+    let dancespiele_db = dancespiele_db?;
 
-    let result = kraken_opr.brain().unwrap_or_else(|err| err.to_string());
-    println!("{}", result);
+    if is_streaming() {
+        run_exchange(
+            StreamingExchange::new(kraken_opr, dancespiele_db.clone()),
+            dancespiele_db,
+            dry_run,
+        )
+    } else {
+        run_exchange(kraken_opr, dancespiele_db, dry_run)
+    }
+}
+
+fn run_exchange<E: Exchange>(
+    exchange: E,
+    dancespiele_db: DancespieleDB,
+    dry_run: bool,
+) -> Result<TickReport> {
+    if dry_run {
+        run_worker(
+            PaperExchange::new(exchange, dancespiele_db.clone()),
+            dancespiele_db,
+        )
+    } else {
+        run_worker(exchange, dancespiele_db)
+    }
 }
 
-fn get_multiples(mult: i32) -> String {
-    let mut multiples: Vec<String> = vec![];
-    for n in (0..60).filter(|r| r % mult == 0).collect::<Vec<i32>>() {
-        let value: String = n.to_string();
-        multiples.push(value);
+fn run_worker<E: Exchange>(exchange: E, dancespiele_db: DancespieleDB) -> Result<TickReport> {
+    let include_fees = env::var("COST_BASIS_FEES")
+        .map(|include_fees| include_fees == "true")
+        .unwrap_or(false);
+    let stop_loss_offset = get_decimal_var("STOP_LOSS_OFFSET", Decimal::new(2, 0))?;
+    let limit_offset = get_decimal_var("STOP_LOSS_LIMIT_OFFSET", Decimal::new(1, 0))?;
+    let mut worker = Worker::new(exchange, dancespiele_db)
+        .with_fees(include_fees)
+        .with_stop_loss_offset(stop_loss_offset)
+        .with_limit_offset(limit_offset);
+
+    if let Ok(userref) = env::var("ORDER_USERREF") {
+        worker = worker.with_userref(userref.parse::<i32>().map_err(|_| {
+            Error::Config(String::from(
+                "ORDER_USERREF must be a number that fits a signed 32 bit integer",
+            ))
+        })?);
     }
 
-    multiples.join(",")
+    worker.brain()
+}
+
+fn get_decimal_var(name: &str, default: Decimal) -> Result<Decimal> {
+    match env::var(name) {
+        Ok(value) => Decimal::from_str(&value)
+            .map_err(|_| Error::Config(format!("{} must be a number", name))),
+        Err(_) => Ok(default),
+    }
 }
@@ -0,0 +1,193 @@
+use crate::dtos::{Action, TickReport};
+use crate::error::Error;
+use rust_decimal::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub struct Metric {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+}
+
+pub const TICKS: Metric = Metric {
+    name: "worker_ticks_total",
+    kind: "counter",
+    help: "Ticks run by the worker by result",
+};
+pub const TICK_DURATION: Metric = Metric {
+    name: "worker_tick_duration_seconds",
+    kind: "gauge",
+    help: "Duration of the last tick",
+};
+pub const PAIR_PRICE: Metric = Metric {
+    name: "worker_pair_price",
+    kind: "gauge",
+    help: "Current price of the pair in the last tick",
+};
+pub const PAIR_BENEFIT: Metric = Metric {
+    name: "worker_pair_benefit_percent",
+    kind: "gauge",
+    help: "Benefit of the pair over its reference price in the last tick",
+};
+pub const STOP_LOSSES: Metric = Metric {
+    name: "worker_stop_losses_total",
+    kind: "counter",
+    help: "Stop losses placed, moved and cancelled by the worker",
+};
+pub const EXCHANGE_ERRORS: Metric = Metric {
+    name: "worker_exchange_errors_total",
+    kind: "counter",
+    help: "Exchange API errors by kind",
+};
+pub const NOTIFICATION_FAILURES: Metric = Metric {
+    name: "worker_notification_failures_total",
+    kind: "counter",
+    help: "Notifications that could not be sent",
+};
+
+const METRICS: [&Metric; 7] = [
+    &TICKS,
+    &TICK_DURATION,
+    &PAIR_PRICE,
+    &PAIR_BENEFIT,
+    &STOP_LOSSES,
+    &EXCHANGE_ERRORS,
+    &NOTIFICATION_FAILURES,
+];
+
+/// Samples by metric name and rendered labels
+static SAMPLES: Mutex<BTreeMap<&str, BTreeMap<String, f64>>> = Mutex::new(BTreeMap::new());
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}=\"{}\"",
+                key,
+                value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn update(metric: &Metric, labels: &[(&str, &str)], update: impl FnOnce(&mut f64)) {
+    if let Ok(mut samples) = SAMPLES.lock() {
+        update(
+            samples
+                .entry(metric.name)
+                .or_default()
+                .entry(format_labels(labels))
+                .or_insert(0.0),
+        );
+    }
+}
+
+pub fn inc(metric: &Metric, labels: &[(&str, &str)]) {
+    update(metric, labels, |value| *value += 1.0);
+}
+
+pub fn set(metric: &Metric, labels: &[(&str, &str)], new_value: f64) {
+    update(metric, labels, |value| *value = new_value);
+}
+
+pub fn record_error(err: &Error) {
+    if let Some(kind) = err.exchange_kind() {
+        inc(&EXCHANGE_ERRORS, &[("kind", kind)]);
+    }
+}
+
+pub fn observe_tick(report: Option<&TickReport>, duration: Duration) {
+    set(&TICK_DURATION, &[], duration.as_secs_f64());
+
+    let report = if let Some(report) = report {
+        inc(&TICKS, &[("result", "success")]);
+        report
+    } else {
+        inc(&TICKS, &[("result", "failure")]);
+        return;
+    };
+
+    report.pairs.iter().for_each(|pair_report| {
+        let labels = [("pair", pair_report.pair.as_str())];
+
+        set(
+            &PAIR_PRICE,
+            &labels,
+            pair_report.current_price.to_f64().unwrap_or(0.0),
+        );
+        set(
+            &PAIR_BENEFIT,
+            &labels,
+            pair_report.benefit.to_f64().unwrap_or(0.0),
+        );
+
+        match pair_report.action {
+            Action::Placed => inc(&STOP_LOSSES, &[("action", "placed")]),
+            Action::Moved => inc(&STOP_LOSSES, &[("action", "moved")]),
+            _ => (),
+        }
+    });
+}
+
+/// Samples in the Prometheus text exposition format
+pub fn render() -> String {
+    let samples = match SAMPLES.lock() {
+        Ok(samples) => samples.clone(),
+        Err(_) => return String::new(),
+    };
+
+    METRICS
+        .iter()
+        .map(|metric| {
+            let header = format!(
+                "# HELP {} {}\n# TYPE {} {}\n",
+                metric.name, metric.help, metric.name, metric.kind
+            );
+
+            samples
+                .get(metric.name)
+                .map(|series| {
+                    series.iter().fold(header.clone(), |text, (labels, value)| {
+                        if labels.is_empty() {
+                            format!("{}{} {}\n", text, metric.name, value)
+                        } else {
+                            format!("{}{}{{{}}} {}\n", text, metric.name, labels, value)
+                        }
+                    })
+                })
+                .unwrap_or(header)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inc, record_error, render, set, EXCHANGE_ERRORS, PAIR_PRICE, STOP_LOSSES};
+    use crate::error::Error;
+    use coinnect::error::{Error as ExchangeError, ErrorKind};
+
+    #[test]
+    fn should_render_metrics_in_text_format() {
+        inc(&STOP_LOSSES, &[("action", "cancelled")]);
+        set(&PAIR_PRICE, &[("pair", "METRICSEUR")], 3.5);
+        record_error(&Error::from(ExchangeError::from_kind(
+            ErrorKind::RateLimitExceeded,
+        )));
+
+        let text = render();
+
+        assert!(text.contains("# TYPE worker_stop_losses_total counter\n"));
+        assert!(text.contains("worker_pair_price{pair=\"METRICSEUR\"} 3.5\n"));
+        assert!(text.contains(&format!(
+            "{}{{kind=\"rate_limit_exceeded\"}}",
+            EXCHANGE_ERRORS.name
+        )));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::error::{Error, Result};
+use crate::logger;
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Every two minutes at second zero, the cadence the worker always had
+const DEFAULT_SCHEDULE: &str = "0 */2 * * * *";
+
+pub enum Schedule {
+    Interval(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    /// Seconds between ticks or a cron expression with seconds (`sec min hour dom month dow`)
+    fn from_str(schedule: &str) -> Result<Self> {
+        if let Ok(seconds) = schedule.trim().parse::<u64>() {
+            return if seconds == 0 {
+                Err(Error::Config(String::from(
+                    "the schedule interval must be greater than zero",
+                )))
+            } else {
+                Ok(Schedule::Interval(Duration::from_secs(seconds)))
+            };
+        }
+
+        cron::Schedule::from_str(schedule)
+            .map(|cron| Schedule::Cron(Box::new(cron)))
+            .map_err(|err| Error::Config(format!("{} is not a valid schedule: {}", schedule, err)))
+    }
+}
+
+/// Clears the running flag when the tick ends, also when it panics
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+pub struct Scheduler {
+    schedule: Schedule,
+    jitter: Duration,
+    running: Arc<AtomicBool>,
+    wakeups: Option<(Receiver<()>, Duration)>,
+}
+
+impl Scheduler {
+    pub fn new(schedule: Schedule) -> Self {
+        Self {
+            schedule,
+            jitter: Duration::from_secs(0),
+            running: Arc::new(AtomicBool::new(false)),
+            wakeups: None,
+        }
+    }
+
+    pub fn default_schedule() -> Schedule {
+        Schedule::from_str(DEFAULT_SCHEDULE).expect("default schedule is valid")
+    }
+
+    /// Delays every tick a random time up to `jitter`
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Runs extra ticks when `wakeups` receives events, at most one every `min_gap`
+    pub fn with_wakeups(mut self, wakeups: Receiver<()>, min_gap: Duration) -> Self {
+        self.wakeups = Some((wakeups, min_gap));
+        self
+    }
+
+    /// Expected time between ticks, the gap between the next two runs for cron schedules
+    pub fn interval(&self) -> Duration {
+        match &self.schedule {
+            Schedule::Interval(interval) => *interval,
+            Schedule::Cron(cron) => {
+                let mut upcoming = cron.upcoming(Utc);
+
+                match (upcoming.next(), upcoming.next()) {
+                    (Some(next), Some(after)) => (after - next).to_std().unwrap_or_default(),
+                    _ => Duration::from_secs(0),
+                }
+            }
+        }
+    }
+
+    fn next_delay(&self, now: DateTime<Utc>) -> Option<Duration> {
+        match &self.schedule {
+            Schedule::Interval(interval) => Some(*interval),
+            Schedule::Cron(cron) => cron
+                .after(&now)
+                .next()
+                .map(|next| (next - now).to_std().unwrap_or_default()),
+        }
+    }
+
+    fn jitter_delay(&self) -> Duration {
+        let jitter_millis = self.jitter.as_millis() as u64;
+
+        if jitter_millis == 0 {
+            return Duration::from_secs(0);
+        }
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.subsec_nanos() as u64)
+            .unwrap_or(0);
+
+        Duration::from_millis(nanos % (jitter_millis + 1))
+    }
+
+    /// Runs the tick in its own thread unless the previous one is still running
+    fn dispatch(&self, tick: fn()) -> Option<JoinHandle<()>> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            logger::warn("Skipping tick, the previous one is still running").emit();
+            return None;
+        }
+
+        let running = RunningGuard(self.running.clone());
+
+        Some(thread::spawn(move || {
+            let _running = running;
+            tick();
+        }))
+    }
+
+    /// Waits until `next_run`, running the wakeups received meanwhile
+    fn wait(&self, next_run: Instant, tick: fn(), last_wakeup: &mut Option<Instant>) {
+        let (wakeups, min_gap) = match &self.wakeups {
+            Some(wakeups) => wakeups,
+            None => {
+                thread::sleep(next_run.saturating_duration_since(Instant::now()));
+                return;
+            }
+        };
+        let mut pending = false;
+
+        loop {
+            let now = Instant::now();
+
+            if now >= next_run {
+                return;
+            }
+
+            let gap_end = last_wakeup.map(|last| last + *min_gap).unwrap_or(now);
+
+            if pending && now >= gap_end {
+                pending = false;
+                *last_wakeup = Some(now);
+                self.dispatch(tick);
+                continue;
+            }
+
+            let until = if pending {
+                gap_end.min(next_run)
+            } else {
+                next_run
+            };
+
+            match wakeups.recv_timeout(until - now) {
+                Ok(()) => pending = true,
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(next_run - now);
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn start(&self, tick: fn()) {
+        let mut last_wakeup = None;
+
+        while let Some(delay) = self.next_delay(Utc::now()) {
+            self.wait(
+                Instant::now() + delay + self.jitter_delay(),
+                tick,
+                &mut last_wakeup,
+            );
+            self.dispatch(tick);
+        }
+
+        logger::warn("The schedule has no upcoming ticks").emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schedule, Scheduler};
+    use chrono::{DateTime, Utc};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    static WAKEUP_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test]
+    fn should_parse_interval_and_cron_schedules() {
+        let interval = Scheduler::new(Schedule::from_str("90").unwrap());
+        let cron = Scheduler::new(Scheduler::default_schedule());
+        let now = DateTime::parse_from_rfc3339("2021-03-01T12:00:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(interval.next_delay(now), Some(Duration::from_secs(90)));
+        assert_eq!(cron.next_delay(now), Some(Duration::from_secs(90)));
+        assert_eq!(cron.interval(), Duration::from_secs(120));
+        assert!(Schedule::from_str("0").is_err());
+        assert!(Schedule::from_str("every two minutes").is_err());
+    }
+
+    #[test]
+    fn should_skip_tick_while_previous_is_running() {
+        let scheduler = Scheduler::new(Schedule::from_str("1").unwrap());
+
+        let tick = scheduler
+            .dispatch(|| thread::sleep(Duration::from_millis(200)))
+            .unwrap();
+
+        assert!(scheduler.dispatch(|| ()).is_none());
+
+        tick.join().unwrap();
+
+        assert!(scheduler.dispatch(|| ()).unwrap().join().is_ok());
+    }
+
+    #[test]
+    fn should_run_ticks_after_one_panics() {
+        let scheduler = Scheduler::new(Schedule::from_str("1").unwrap());
+
+        let tick = scheduler.dispatch(|| panic!("tick failed")).unwrap();
+
+        assert!(tick.join().is_err());
+        assert!(scheduler.dispatch(|| ()).unwrap().join().is_ok());
+    }
+
+    #[test]
+    fn should_keep_jitter_within_bounds() {
+        let scheduler =
+            Scheduler::new(Schedule::from_str("60").unwrap()).with_jitter(Duration::from_secs(5));
+
+        assert!(scheduler.jitter_delay() <= Duration::from_secs(5));
+        assert_eq!(
+            Scheduler::new(Schedule::from_str("60").unwrap()).jitter_delay(),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn should_throttle_ticks_on_wakeups() {
+        let (sender, receiver) = channel();
+        let scheduler = Scheduler::new(Schedule::from_str("60").unwrap())
+            .with_wakeups(receiver, Duration::from_millis(50));
+
+        (0..3).for_each(|_| sender.send(()).unwrap());
+
+        scheduler.wait(
+            Instant::now() + Duration::from_millis(200),
+            || {
+                WAKEUP_TICKS.fetch_add(1, Ordering::SeqCst);
+            },
+            &mut None,
+        );
+        thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(WAKEUP_TICKS.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::health;
+use crate::logger;
+use crate::metrics;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// Clients that do not send the request line in time are dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+fn route(path: &str) -> Response {
+    match path {
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body: metrics::render(),
+        },
+        "/healthz" => Response {
+            status: "200 OK",
+            content_type: "application/json",
+            body: String::from("{\"status\":\"alive\"}"),
+        },
+        "/readyz" => {
+            let readiness = health::readiness();
+
+            Response {
+                status: if readiness.ready {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                },
+                content_type: "application/json",
+                body: serde_json::to_string(&readiness).unwrap_or_default(),
+            }
+        }
+        _ => Response {
+            status: "404 Not Found",
+            content_type: "text/plain",
+            body: String::from("not found\n"),
+        },
+    }
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let response = route(path.split('?').next().unwrap_or(path));
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// Serves the worker endpoints in a background thread, the scheduler keeps the main one
+pub fn serve(addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+
+    logger::info("HTTP server listening")
+        .field("addr", listener.local_addr()?.to_string())
+        .emit();
+
+    listen(listener);
+
+    Ok(())
+}
+
+/// Answers every connection in its own thread so a slow client does not block the others
+fn listen(listener: TcpListener) {
+    thread::spawn(move || {
+        listener.incoming().for_each(|stream| match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(err) = handle(stream) {
+                        warn_request_failed(err);
+                    }
+                });
+            }
+            Err(err) => warn_request_failed(err),
+        })
+    });
+}
+
+fn warn_request_failed(err: std::io::Error) {
+    logger::warn("HTTP request could not be answered")
+        .field("error", err.to_string())
+        .emit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle, listen};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    fn get(path: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || handle(listener.accept().unwrap().0).unwrap());
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+
+        response
+    }
+
+    #[test]
+    fn should_serve_metrics() {
+        let response = get("/metrics");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("# TYPE worker_ticks_total counter"));
+        assert!(get("/unknown").starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[test]
+    fn should_answer_while_a_client_stays_idle() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        listen(listener);
+
+        let _idle = TcpStream::connect(addr).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn should_serve_health_and_readiness() {
+        let healthz = get("/healthz");
+        let readyz = get("/readyz");
+
+        assert!(healthz.starts_with("HTTP/1.1 200 OK"));
+        assert!(healthz.ends_with("{\"status\":\"alive\"}"));
+        assert!(readyz.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(readyz.contains("\"checks\":{"));
+    }
+}
@@ -1,2 +1,2 @@
 mod notify;
-pub use notify::send_notification;
+pub use notify::{send_fill_notification, send_notification};
@@ -1,4 +1,6 @@
-use crate::kraken::dtos::{Notify, NotifyEmail};
+use crate::dtos::{Notify, NotifyEmail, NotifyFill, NotifyFillEmail};
+use crate::error::{Error, Result};
+use crate::logger;
 use celery::TaskResult;
 use std::env;
 
@@ -7,33 +9,68 @@ fn add_stop_loss(notify: NotifyEmail) -> TaskResult<NotifyEmail> {
     Ok(notify)
 }
 
-pub async fn send_notification(notify: Notify) {
-    let email = env::var("EMAIL").expect("Email should be set");
+#[celery::task]
+fn stop_executed(notify: NotifyFillEmail) -> TaskResult<NotifyFillEmail> {
+    Ok(notify)
+}
+
+macro_rules! notification_app {
+    () => {{
+        let amrq_addr =
+            std::env::var("AMPQ_ADDR").unwrap_or_else(|_| "amqp://127.0.0.1:5672".into());
 
-    let notify_email = NotifyEmail::from((notify, email));
+        celery::app!(
+            broker = AMQP { amrq_addr },
+            tasks = [
+                add_stop_loss,
+                stop_executed,
+            ],
+            task_routes = [
+                "add_stop_loss" => "stop_loss_queue",
+                "stop_executed" => "stop_loss_queue",
+        ])
+    }};
+}
+
+fn get_email() -> Result<String> {
+    env::var("EMAIL").map_err(|_| Error::Config(String::from("EMAIL must be set")))
+}
 
-    let amrq_addr = std::env::var("AMPQ_ADDR").unwrap_or_else(|_| "amqp://127.0.0.1:5672".into());
-    let notification = celery::app!(
-        broker = AMQP { amrq_addr },
-        tasks = [
-            add_stop_loss,
-        ],
-        task_routes = [
-            "add_stop_loss" => "stop_loss_queue",
-    ]);
+pub async fn send_notification(notify: Notify) -> Result<()> {
+    let notify_email = NotifyEmail::from((notify, get_email()?));
 
-    let task_id = notification
+    let task_id = notification_app!()
         .send_task(add_stop_loss::new(notify_email))
         .await
-        .unwrap();
+        .map_err(|err| Error::Notification(err.to_string()))?;
+
+    logger::debug("Email task sent")
+        .field("task_id", task_id)
+        .emit();
+
+    Ok(())
+}
+
+/// Notifies that a stop loss of the worker was executed
+pub async fn send_fill_notification(notify: NotifyFill) -> Result<()> {
+    let notify_email = NotifyFillEmail::from((notify, get_email()?));
+
+    let task_id = notification_app!()
+        .send_task(stop_executed::new(notify_email))
+        .await
+        .map_err(|err| Error::Notification(err.to_string()))?;
+
+    logger::debug("Stop executed email task sent")
+        .field("task_id", task_id)
+        .emit();
 
-    println!("Email task with id {} sent", task_id)
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::send_notification;
-    use crate::kraken::dtos::Notify;
+    use crate::dtos::Notify;
     use agnostik::prelude::*;
     use dotenv::dotenv;
 
@@ -45,14 +82,13 @@ mod tests {
             pair: "KAVAEUR".to_string(),
             price: "4.0".to_string(),
             benefit: "40.0".to_string(),
+            dry_run: false,
         };
 
         let runtime = Agnostik::tokio();
 
-        let notification = runtime.spawn(async move {
-            send_notification(notify).await;
-        });
+        let notification = runtime.spawn(async move { send_notification(notify).await });
 
-        runtime.block_on(notification);
+        runtime.block_on(notification).unwrap();
     }
 }
@@ -1,44 +1,62 @@
-pub fn substract_pair(pair: &str) -> String {
-    let mut current_currency = String::from("");
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
 
-    let current_currencies = vec![
-        "XBT", "ETH", "USDT", "XRP", "LINK", "BCH", "LTC", "DOT", "ADA", "USDC", "EOS", "XMR",
-        "TRX", "XLM", "XTZ", "ATOM", "DAI", "FIL", "UNI", "DASH", "ZEC", "ETC", "YFI", "OMG",
-        "COMP", "SNX", "WAVES", "XDG", "KSM", "ALGO", "BAT", "ICX", "QTUM", "KNC", "REP", "REPV2",
-        "LSK", "SC", "NANO", "BAL", "OXT", "CRV", "PAXG", "STORJ", "KAVA", "GNO", "MLN", "KEEP",
-        "TBTC", "ANT", "MANA", "AAVE", "GRT"
-    ];
+pub fn normalize_asset(asset: &str) -> String {
+    if asset.len() == 4 && (asset.starts_with('X') || asset.starts_with('Z')) {
+        asset[1..].to_string()
+    } else {
+        asset.to_string()
+    }
+}
 
-    current_currencies.into_iter().for_each(|c| {
-        if pair.starts_with(c) {
-            current_currency = c.to_string();
-        }
-    });
+pub fn get_asset_balance(balance: &HashMap<String, String>, asset: &str) -> Option<Decimal> {
+    let quantity = balance.get(asset).or_else(|| {
+        balance
+            .iter()
+            .find(|(key, _quantity)| normalize_asset(key) == normalize_asset(asset))
+            .map(|(_key, quantity)| quantity)
+    })?;
 
-    current_currency
+    Decimal::from_str(quantity).ok()
 }
 
 #[test]
-fn should_substract_the_currency_from_pair() {
-    let currencies_to_change = vec![
-        "EUR", "USD", "XBT", "ETH", "CAD", "JPY", "CHF", "GBP", "USDT", "USDC", "DAI", "AUD",
-    ];
-    let current_currencies = vec![
-        "XBT", "ETH", "USDT", "XRP", "LINK", "BCH", "LTC", "DOT", "ADA", "USDC", "EOS", "XMR",
-        "TRX", "XLM", "XTZ", "ATOM", "DAI", "FIL", "UNI", "DASH", "ZEC", "ETC", "YFI", "OMG",
-        "COMP", "SNX", "WAVES", "XDG", "KSM", "ALGO", "BAT", "ICX", "QTUM", "KNC", "REP", "REPV2",
-        "LSK", "SC", "NANO", "BAL", "OXT", "CRV", "PAXG", "STORJ", "KAVA", "GNO", "MLN","KEEP",
-        "TBTC", "ANT", "MANA", "AAVE", "GRT"
+fn should_normalize_legacy_assets() {
+    let assets = vec![
+        ("XXBT", "XBT"),
+        ("XETH", "ETH"),
+        ("ZEUR", "EUR"),
+        ("ZUSD", "USD"),
+        ("XXDG", "XDG"),
+        ("KAVA", "KAVA"),
+        ("XTZ", "XTZ"),
+        ("REPV2", "REPV2"),
+        ("USDT", "USDT"),
+        ("USDC", "USDC"),
     ];
 
-    current_currencies.into_iter().for_each(|cc| {
-        currencies_to_change.clone().into_iter().for_each(|ctc| {
-            let mut cc_copy = cc.to_string();
-            cc_copy.push_str(ctc);
+    assets.into_iter().for_each(|(asset, normalized)| {
+        assert_eq!(normalize_asset(asset), normalized);
+    });
+}
 
-            let currency = substract_pair(&cc_copy);
+#[test]
+fn should_get_asset_balance() {
+    let mut balance = HashMap::new();
+    balance.insert(String::from("XXBT"), String::from("0.5"));
+    balance.insert(String::from("REPV2"), String::from("10.0"));
+    balance.insert(String::from("USDT"), String::from("100"));
 
-            assert_eq!(currency, cc);
-        })
-    });
+    assert_eq!(
+        get_asset_balance(&balance, "XXBT"),
+        Some(Decimal::new(5, 1))
+    );
+    assert_eq!(get_asset_balance(&balance, "XBT"), Some(Decimal::new(5, 1)));
+    assert_eq!(
+        get_asset_balance(&balance, "REPV2"),
+        Some(Decimal::new(100, 1))
+    );
+    assert_eq!(get_asset_balance(&balance, "XREP"), None);
+    assert_eq!(get_asset_balance(&balance, "USDC"), None);
 }
@@ -0,0 +1,70 @@
+use crate::exchange::types::Ohlc;
+use rust_decimal::prelude::*;
+
+/// Average true range with Wilder's smoothing, `None` without a candle more than the period
+pub fn calc_average_true_range(candles: &[Ohlc], period: usize) -> Option<Decimal> {
+    if period == 0 || candles.len() <= period {
+        return None;
+    }
+
+    let true_ranges: Vec<Decimal> = candles
+        .windows(2)
+        .map(|window| {
+            let (previous, candle) = (&window[0], &window[1]);
+
+            (candle.high - candle.low)
+                .max((candle.high - previous.close).abs())
+                .max((candle.low - previous.close).abs())
+        })
+        .collect();
+    let period_decimal = Decimal::from(period as u64);
+    let first_average = true_ranges[..period].iter().sum::<Decimal>() / period_decimal;
+
+    Some(
+        true_ranges[period..]
+            .iter()
+            .fold(first_average, |average, true_range| {
+                (average * (period_decimal - Decimal::ONE) + true_range) / period_decimal
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::calc_average_true_range;
+    use crate::exchange::types::Ohlc;
+    use rust_decimal::Decimal;
+
+    fn candle(high: i64, low: i64, close: i64) -> Ohlc {
+        Ohlc {
+            time: 0,
+            open: Decimal::from(close),
+            high: Decimal::from(high),
+            low: Decimal::from(low),
+            close: Decimal::from(close),
+            vwap: Decimal::from(close),
+            volume: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn should_calculate_average_true_range() {
+        let candles = vec![
+            candle(10, 8, 9),
+            candle(11, 9, 10),
+            candle(12, 9, 11),
+            candle(11, 10, 10),
+            candle(14, 10, 13),
+        ];
+
+        assert_eq!(
+            calc_average_true_range(&candles[..4], 3),
+            Some(Decimal::new(2, 0))
+        );
+        assert_eq!(
+            calc_average_true_range(&candles, 3),
+            Some(Decimal::new(8, 0) / Decimal::new(3, 0))
+        );
+        assert_eq!(calc_average_true_range(&candles, 5), None);
+    }
+}
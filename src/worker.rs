@@ -0,0 +1,2507 @@
+use crate::cost_basis::calc_cost_basis;
+use crate::db::{AssetPairsCache, DancespieleDB, Percentage, StopReplacement};
+use crate::dtos::{
+    Action, CurrentPrice, FutureOperation, Notify, NotifyFill, PairReport, StopFill,
+    StopLossActive, TickReport,
+};
+use crate::error::{Error, Result};
+use crate::exchange::types::{
+    get_operation_type, get_order_type, AssetPair, Ohlc, OperationType, Order, OrderRequest,
+    OrderType, PriceSource, Trade,
+};
+use crate::exchange::Exchange;
+use crate::logger;
+use crate::metrics;
+use crate::services::{send_fill_notification, send_notification};
+use crate::utils::get_asset_balance;
+use crate::volatility::calc_average_true_range;
+use agnostik::prelude::*;
+use chrono::Utc;
+use rust_decimal::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::future::Future;
+
+const ASSET_PAIRS_TTL: i64 = 86400;
+const STOP_REPLACEMENTS_KEPT: usize = 100;
+const STOP_FILLS_KEPT: usize = 100;
+
+pub struct Worker<E: Exchange> {
+    exchange: E,
+    dancespiele_db: DancespieleDB,
+    percentages: Vec<Percentage>,
+    asset_pairs: HashMap<String, AssetPair>,
+    include_fees: bool,
+    stop_loss_offset: Decimal,
+    limit_offset: Decimal,
+    userref: Option<i32>,
+    notify: bool,
+    /// Candles read this tick for the price of the pair, the volatility reuses them
+    candles: HashMap<String, Vec<Ohlc>>,
+}
+
+impl<E: Exchange> Worker<E> {
+    pub fn new(exchange: E, dancespiele_db: DancespieleDB) -> Self {
+        let mut dancespiele_db = if exchange.dry_run() {
+            dancespiele_db.for_dry_run()
+        } else {
+            dancespiele_db
+        };
+        let percentages = dancespiele_db
+            .fetch_coins_percentages_stop_loss()
+            .unwrap_or_default();
+
+        Self {
+            exchange,
+            dancespiele_db,
+            percentages,
+            asset_pairs: HashMap::new(),
+            include_fees: false,
+            stop_loss_offset: Decimal::new(2, 0),
+            limit_offset: Decimal::new(1, 0),
+            userref: None,
+            notify: true,
+            candles: HashMap::new(),
+        }
+    }
+
+    pub fn with_fees(mut self, include_fees: bool) -> Self {
+        self.include_fees = include_fees;
+        self
+    }
+
+    pub fn with_stop_loss_offset(mut self, stop_loss_offset: Decimal) -> Self {
+        self.stop_loss_offset = stop_loss_offset;
+        self
+    }
+
+    pub fn with_limit_offset(mut self, limit_offset: Decimal) -> Self {
+        self.limit_offset = limit_offset;
+        self
+    }
+
+    /// Tags the orders placed by the worker, only orders with this tag are managed
+    pub fn with_userref(mut self, userref: i32) -> Self {
+        self.userref = Some(userref);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn without_notifications(mut self) -> Self {
+        self.notify = false;
+        self
+    }
+
+    fn is_own_order(&self, order: &Order) -> bool {
+        self.userref.is_none() || order.userref == self.userref
+    }
+
+    fn load_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>> {
+        let now = Utc::now().timestamp();
+        let cache_opt = self.dancespiele_db.fetch_asset_pairs()?;
+
+        if let Some(cache) = cache_opt {
+            if now - cache.time < ASSET_PAIRS_TTL {
+                return Ok(cache.asset_pairs);
+            }
+        }
+
+        let asset_pairs = self.exchange.get_asset_pairs()?;
+
+        self.dancespiele_db.save_asset_pairs(&AssetPairsCache {
+            time: now,
+            asset_pairs: asset_pairs.clone(),
+        })?;
+
+        Ok(asset_pairs)
+    }
+
+    fn get_asset_pairs(&mut self) -> Result<HashMap<String, AssetPair>> {
+        if self.asset_pairs.is_empty() {
+            self.asset_pairs = self.load_asset_pairs()?;
+        }
+
+        Ok(self.asset_pairs.clone())
+    }
+
+    fn get_asset_pair(&mut self, pair: &str) -> Result<AssetPair> {
+        find_asset_pair(&self.get_asset_pairs()?, pair)
+            .ok_or_else(|| Error::Config(format!("{} does not exist in the asset pairs", pair)))
+    }
+
+    fn get_percentage(&self, pair: &str) -> Result<Percentage> {
+        self.percentages
+            .iter()
+            .find(|p| p.pair == pair)
+            .cloned()
+            .ok_or_else(|| Error::Config(format!("{} does not exist in the database", pair)))
+    }
+
+    fn canonical_percentages(&mut self) -> Result<Vec<Percentage>> {
+        let asset_pairs = self.get_asset_pairs()?;
+
+        Ok(self
+            .percentages
+            .clone()
+            .into_iter()
+            .map(|mut percentage| {
+                if let Some(asset_pair) = find_asset_pair(&asset_pairs, &percentage.pair) {
+                    percentage.pair = asset_pair.altname;
+                }
+
+                percentage
+            })
+            .collect())
+    }
+
+    /// Trades history stored in sled. The first syncs backfill it saving every page, so a
+    /// failure resumes from the page it stopped at, then only the new trades are read
+    fn sync_trades(&mut self) -> Result<HashMap<String, Trade>> {
+        let mut trades = self.dancespiele_db.fetch_trades()?;
+        let mut trades_sync = self.dancespiele_db.fetch_trades_sync()?;
+
+        if !trades_sync.complete {
+            loop {
+                let trades_page = self.exchange.get_trades(trades_sync.offset)?;
+                let page_size = trades_page.trades.len() as u32;
+
+                trades.extend(trades_page.trades);
+                trades_sync.offset += page_size;
+                trades_sync.complete = page_size == 0 || trades_sync.offset >= trades_page.count;
+
+                self.dancespiele_db.save_trades(&trades)?;
+                self.dancespiele_db.save_trades_sync(&trades_sync)?;
+
+                if trades_sync.complete {
+                    return Ok(trades);
+                }
+            }
+        }
+
+        let mut offset = 0;
+
+        loop {
+            let trades_page = self.exchange.get_trades(offset)?;
+            let page_size = trades_page.trades.len() as u32;
+            let mut already_seen = false;
+
+            trades_page.trades.into_iter().for_each(|(id, trade)| {
+                if let Entry::Vacant(entry) = trades.entry(id) {
+                    entry.insert(trade);
+                } else {
+                    already_seen = true;
+                }
+            });
+
+            offset += page_size;
+
+            if already_seen || page_size == 0 || offset >= trades_page.count {
+                break;
+            }
+        }
+
+        // Saved once every new trade was read, a partial save would stop the next sync early
+        self.dancespiele_db.save_trades(&trades)?;
+
+        Ok(trades)
+    }
+
+    /// Positions with a balance and their cost basis, the pairs whose trades could not be
+    /// parsed are returned apart with the error
+    fn get_buy_prices(&mut self) -> Result<(Vec<FutureOperation>, HashMap<String, Error>)> {
+        let trades_active = self.sync_trades()?;
+        let current_balance = self.exchange.get_current_balance()?;
+        let asset_pairs = self.get_asset_pairs()?;
+
+        let trades = trades_active
+            .into_values()
+            .filter_map(|mut trade| {
+                trade.pair = find_asset_pair(&asset_pairs, &trade.pair)?.altname;
+                Some(trade)
+            })
+            .collect();
+
+        let mut trades_to_operate = vec![];
+        let mut trade_errors = HashMap::new();
+
+        calc_cost_basis(trades, self.include_fees)
+            .into_iter()
+            .for_each(|(pair, cost_basis)| {
+                let quantity = find_asset_pair(&asset_pairs, &pair)
+                    .and_then(|asset_pair| get_asset_balance(&current_balance, &asset_pair.base))
+                    .filter(|quantity| *quantity > Decimal::new(1, 5));
+
+                match (cost_basis, quantity) {
+                    (Ok(cost_basis), Some(quantity)) if !cost_basis.quantity.is_zero() => {
+                        trades_to_operate.push(FutureOperation::from((cost_basis, quantity)))
+                    }
+                    (Err(err), Some(_quantity)) => {
+                        trade_errors.insert(pair, err);
+                    }
+                    _ => (),
+                }
+            });
+
+        Ok((trades_to_operate, trade_errors))
+    }
+
+    fn calc_benefit(&mut self, price_ordered: Decimal, current_price: Decimal) -> Decimal {
+        let result = current_price - price_ordered;
+
+        if result.is_sign_negative() || price_ordered.is_zero() {
+            Decimal::ZERO
+        } else {
+            (result / price_ordered * Decimal::new(100, 0))
+                .round_dp_with_strategy(2, RoundingStrategy::ToZero)
+        }
+    }
+
+    fn calc_stop_loss_price(&mut self, current_price: Decimal, offset: Decimal) -> Decimal {
+        (current_price - current_price * offset / Decimal::new(100, 0)).normalize()
+    }
+
+    /// Moves an open order editing it in place when the exchange allows it, otherwise
+    /// cancels it and places the new one, placing the previous order again if that fails
+    fn replace_order(&mut self, txid: &str, order: OrderRequest) -> Result<()> {
+        let pair = order.pair.clone();
+        let price = order.price.clone();
+
+        match self.exchange.edit_order(txid, order.clone()) {
+            Ok(true) => {
+                self.record_stop_replacement(txid, &pair, &price, "edited", None)?;
+                return Ok(());
+            }
+            Ok(false) => (),
+            Err(err) => {
+                // A failed edit leaves the previous order on the book
+                logger::error("Stop loss could not be edited")
+                    .pair(&pair)
+                    .order(txid)
+                    .field("price", &price)
+                    .field("error", err.to_string())
+                    .emit();
+                self.record_stop_replacement(
+                    txid,
+                    &pair,
+                    &price,
+                    "edit_failed",
+                    Some(err.to_string()),
+                )?;
+
+                return Err(err);
+            }
+        }
+
+        let previous_order = self
+            .exchange
+            .get_active_orders()?
+            .open
+            .remove(txid)
+            .map(OrderRequest::from);
+
+        self.exchange.cancel_order(txid)?;
+
+        if let Err(err) = self.exchange.add_order(order) {
+            let outcome = match previous_order.map(|previous| self.exchange.add_order(previous)) {
+                Some(Ok(())) => "restored",
+                _ => "unprotected",
+            };
+
+            logger::error("Stop loss could not be moved")
+                .pair(&pair)
+                .order(txid)
+                .field("price", &price)
+                .field("outcome", outcome)
+                .field("error", err.to_string())
+                .emit();
+            self.record_stop_replacement(txid, &pair, &price, outcome, Some(err.to_string()))?;
+
+            return Err(err);
+        }
+
+        self.record_stop_replacement(txid, &pair, &price, "replaced", None)
+    }
+
+    fn record_stop_replacement(
+        &mut self,
+        txid: &str,
+        pair: &str,
+        price: &str,
+        outcome: &str,
+        error: Option<String>,
+    ) -> Result<()> {
+        let mut replacements = self.dancespiele_db.fetch_stop_replacements()?;
+
+        replacements.push(StopReplacement {
+            txid: txid.to_string(),
+            pair: pair.to_string(),
+            price: price.to_string(),
+            outcome: outcome.to_string(),
+            error,
+            time: Utc::now().timestamp(),
+        });
+
+        let keep_from = replacements.len().saturating_sub(STOP_REPLACEMENTS_KEPT);
+
+        self.dancespiele_db
+            .save_stop_replacements(&replacements[keep_from..])
+    }
+
+    /// Positions with the volume not committed to take profits or to sell orders placed by
+    /// others, the volume stop losses protect. Kraken holds the balance of every open sell, so
+    /// a stop over it would be rejected
+    fn free_volume(
+        &self,
+        buy_prices: &[FutureOperation],
+        active_orders: &HashMap<String, Order>,
+    ) -> Vec<FutureOperation> {
+        buy_prices
+            .iter()
+            .cloned()
+            .map(|mut buy_price| {
+                let committed: Decimal = active_orders
+                    .values()
+                    .filter(|order| {
+                        order.pair == buy_price.pair
+                            && if self.is_own_order(order) {
+                                is_sell_order(
+                                    order,
+                                    vec![OrderType::TakeProfit, OrderType::TakeProfitLimit],
+                                )
+                            } else {
+                                order.operation_type == get_operation_type(OperationType::SELL)
+                            }
+                    })
+                    .map(|order| order.get_remaining_volume())
+                    .sum();
+
+                buy_price.quantity = (buy_price.quantity - committed).max(Decimal::ZERO);
+                buy_price
+            })
+            .collect()
+    }
+
+    /// Leaves a single stop loss per pair covering the whole volume to protect, keeping the
+    /// highest price when a pair has several of them after partial fills or manual splits
+    fn reconcile_stop_losses(
+        &mut self,
+        buy_price: &FutureOperation,
+        active_orders: &HashMap<String, Order>,
+    ) -> Result<bool> {
+        let mut stop_losses: Vec<(String, Order)> = active_orders
+            .iter()
+            .filter(|(_txid, order)| {
+                order.pair == buy_price.pair
+                    && self.is_own_order(order)
+                    && is_sell_order(order, vec![OrderType::StopLoss, OrderType::StopLossLimit])
+            })
+            .map(|(txid, order)| (txid.clone(), order.clone()))
+            .collect();
+
+        if stop_losses.is_empty() {
+            return Ok(false);
+        }
+
+        let asset_pair = self.get_asset_pair(&buy_price.pair)?;
+
+        let volume = asset_pair.round_volume(buy_price.quantity);
+        let protected_volume: Decimal = stop_losses
+            .iter()
+            .map(|(_txid, order)| order.get_remaining_volume())
+            .sum();
+
+        if stop_losses.len() == 1 && protected_volume == volume {
+            return Ok(false);
+        }
+
+        if volume.is_zero() || volume < asset_pair.get_ordermin() {
+            logger::warn("Skipping stop losses reconciliation below the minimum order size")
+                .pair(&buy_price.pair)
+                .field("volume", volume)
+                .field("ordermin", asset_pair.get_ordermin())
+                .emit();
+            return Ok(false);
+        }
+
+        logger::info("Reconciling stop losses")
+            .pair(&buy_price.pair)
+            .field("stop_losses", stop_losses.len())
+            .field("protected_volume", protected_volume)
+            .field("volume", volume)
+            .emit();
+
+        stop_losses.sort_by(|(_txid_a, a), (_txid_b, b)| {
+            Decimal::from_str(&b.price)
+                .unwrap_or(Decimal::ZERO)
+                .cmp(&Decimal::from_str(&a.price).unwrap_or(Decimal::ZERO))
+        });
+
+        let (txid, order) = stop_losses.remove(0);
+        let cancelled: Vec<(String, Order)> = stop_losses
+            .into_iter()
+            .filter(|(extra_txid, _order)| {
+                self.exchange
+                    .cancel_order(extra_txid)
+                    .map(|_| metrics::inc(&metrics::STOP_LOSSES, &[("action", "cancelled")]))
+                    .map_err(|err| {
+                        metrics::record_error(&err);
+                        logger::error("Stop loss could not be cancelled")
+                            .pair(&buy_price.pair)
+                            .order(extra_txid)
+                            .field("error", err.to_string())
+                            .emit()
+                    })
+                    .is_ok()
+            })
+            .collect();
+
+        if let Err(err) = self.replace_order(
+            &txid,
+            OrderRequest {
+                volume: volume.to_string(),
+                ..OrderRequest::from(order)
+            },
+        ) {
+            cancelled.into_iter().for_each(|(extra_txid, extra_order)| {
+                if let Err(err) = self.exchange.add_order(OrderRequest::from(extra_order)) {
+                    metrics::record_error(&err);
+                    logger::error("Stop loss could not be placed again")
+                        .pair(&buy_price.pair)
+                        .order(&extra_txid)
+                        .field("error", err.to_string())
+                        .emit();
+                }
+            });
+
+            return Err(err);
+        }
+
+        Ok(true)
+    }
+
+    /// Places the take profit of the position once, freeing the volume it needs
+    /// from the stop loss so both orders never sell more than the balance
+    fn add_take_profit(
+        &mut self,
+        buy_price: &FutureOperation,
+        active_orders: &HashMap<String, Order>,
+    ) -> Result<bool> {
+        let percentage = self.get_percentage(&buy_price.pair)?;
+
+        let take_profit = if let Some(take_profit) = percentage.get_take_profit()? {
+            take_profit
+        } else {
+            return Ok(false);
+        };
+
+        let pair_orders: Vec<(String, Order)> = active_orders
+            .iter()
+            .filter(|(_txid, order)| order.pair == buy_price.pair && self.is_own_order(order))
+            .map(|(txid, order)| (txid.clone(), order.clone()))
+            .collect();
+
+        if pair_orders.iter().any(|(_txid, order)| {
+            is_sell_order(
+                order,
+                vec![OrderType::TakeProfit, OrderType::TakeProfitLimit],
+            )
+        }) {
+            return Ok(false);
+        }
+
+        let mut take_profits = self.dancespiele_db.fetch_take_profits()?;
+
+        if take_profits.get(&buy_price.pair) == Some(&buy_price.operation_time) {
+            return Ok(false);
+        }
+
+        let asset_pair = self.get_asset_pair(&buy_price.pair)?;
+
+        let take_profit_price = asset_pair.round_price(
+            buy_price.buy_price + buy_price.buy_price * take_profit / Decimal::new(100, 0),
+        );
+        let order_type = percentage.get_take_profit_order_type()?;
+        let limit_price = match order_type {
+            OrderType::TakeProfitLimit => {
+                let limit_offset = percentage.get_limit_offset(self.limit_offset)?;
+
+                Some(
+                    asset_pair
+                        .round_price(self.calc_stop_loss_price(take_profit_price, limit_offset))
+                        .to_string(),
+                )
+            }
+            _ => None,
+        };
+        let volume = asset_pair.round_volume(
+            buy_price.quantity * percentage.get_take_profit_volume()? / Decimal::new(100, 0),
+        );
+
+        if volume.is_zero() || volume < asset_pair.get_ordermin() {
+            logger::warn("Skipping take profit below the minimum order size")
+                .pair(&buy_price.pair)
+                .field("volume", volume)
+                .field("ordermin", asset_pair.get_ordermin())
+                .emit();
+            return Ok(false);
+        }
+
+        let remaining = asset_pair.round_volume(buy_price.quantity - volume);
+        let stop_loss_opt = pair_orders.into_iter().find(|(_txid, order)| {
+            is_sell_order(order, vec![OrderType::StopLoss, OrderType::StopLossLimit])
+                && order.get_remaining_volume() > remaining
+        });
+
+        if let Some((txid, order)) = stop_loss_opt {
+            if remaining.is_zero() || remaining < asset_pair.get_ordermin() {
+                logger::warn(
+                    "Skipping take profit, remaining stop loss below the minimum order size",
+                )
+                .pair(&buy_price.pair)
+                .order(&txid)
+                .field("volume", remaining)
+                .field("ordermin", asset_pair.get_ordermin())
+                .emit();
+                return Ok(false);
+            }
+
+            self.replace_order(
+                &txid,
+                OrderRequest {
+                    volume: remaining.to_string(),
+                    ..OrderRequest::from(order)
+                },
+            )?;
+        }
+
+        self.exchange.add_order(OrderRequest {
+            pair: buy_price.pair.clone(),
+            operation_type: get_operation_type(OperationType::SELL),
+            order_type: get_order_type(order_type),
+            price: take_profit_price.to_string(),
+            price2: limit_price,
+            volume: volume.to_string(),
+            userref: self.userref,
+        })?;
+
+        take_profits.insert(buy_price.pair.clone(), buy_price.operation_time);
+        self.dancespiele_db.save_take_profits(&take_profits)?;
+
+        Ok(true)
+    }
+
+    /// Stop offset and trailing threshold in percent when the stop distance of the pair follows
+    /// its volatility. The stop sits `atr_multiplier` average true ranges below the price and
+    /// moves once it can go up at least one more average true range
+    fn get_volatility(
+        &mut self,
+        percentage: &Percentage,
+        price: Decimal,
+    ) -> Result<Option<(Decimal, Decimal)>> {
+        let multiplier = match percentage.get_atr_multiplier()? {
+            Some(multiplier) => multiplier,
+            None => return Ok(None),
+        };
+        let period = percentage.get_atr_period()?;
+        let candles = match self.candles.remove(&percentage.pair) {
+            Some(candles) => candles,
+            None => self
+                .exchange
+                .get_ohlc(&percentage.pair, percentage.get_price_interval()?)?,
+        };
+
+        let average_true_range = match calc_average_true_range(&candles, period) {
+            Some(average_true_range) if !price.is_zero() => average_true_range,
+            _ => {
+                logger::warn(
+                    "Not enough candles for the average true range, using the fixed offset",
+                )
+                .pair(&percentage.pair)
+                .field("candles", candles.len())
+                .emit();
+                return Ok(None);
+            }
+        };
+
+        let distance = average_true_range * multiplier;
+
+        if distance >= price {
+            logger::warn("Average true range stop would not be over zero, using the fixed offset")
+                .pair(&percentage.pair)
+                .field("distance", distance)
+                .field("price", price)
+                .emit();
+            return Ok(None);
+        }
+
+        let highest_stop_to_move = price - distance - average_true_range;
+        let next_stop_loss = if highest_stop_to_move > Decimal::ZERO {
+            self.calc_benefit(highest_stop_to_move, price)
+        } else {
+            Decimal::MAX
+        };
+
+        Ok(Some((
+            distance / price * Decimal::new(100, 0),
+            next_stop_loss,
+        )))
+    }
+
+    fn add_stop_loss(
+        &mut self,
+        buy_price: FutureOperation,
+        current_assest: CurrentPrice,
+        benefit: Decimal,
+        stop_loss_opt: Option<(String, Decimal)>,
+    ) -> Result<(Action, Option<Decimal>)> {
+        let mut send_order = false;
+        let mut order_to_cancel = None;
+        let mut stop_loss_price = Decimal::ZERO;
+        let percentage_to_stop_loss = self.get_percentage(&current_assest.pair)?;
+        let volatility = self.get_volatility(&percentage_to_stop_loss, current_assest.price)?;
+        let stop_loss_offset = match volatility {
+            Some((offset, _next_stop_loss)) => offset,
+            None => percentage_to_stop_loss.get_stop_loss_offset(self.stop_loss_offset)?,
+        };
+
+        let asset_pair = self.get_asset_pair(&current_assest.pair)?;
+        let break_even = percentage_to_stop_loss.get_break_even()?;
+        let mut break_evens = self.dancespiele_db.fetch_break_evens()?;
+        let break_even_active = break_evens.get(&buy_price.pair) == Some(&buy_price.operation_time);
+        // Rounded up, a break even stop below entry plus fees would lock in a loss
+        let break_even_price = asset_pair.round_price_up(buy_price.break_even_price);
+        let break_even_reached = match break_even {
+            Some(break_even) => {
+                break_even <= self.calc_benefit(buy_price.buy_price, current_assest.price)
+                    && break_even_price < current_assest.price
+            }
+            None => false,
+        };
+        let mut break_even_stop = false;
+
+        if let Some((order, order_price)) = stop_loss_opt {
+            if break_even_active {
+                // The break even stop is active, the ladder starts as if there was no stop
+                let benefit = self.calc_benefit(buy_price.buy_price, current_assest.price);
+                let new_stop_loss_price = asset_pair
+                    .round_price(self.calc_stop_loss_price(current_assest.price, stop_loss_offset));
+
+                if Decimal::from_str(&percentage_to_stop_loss.new_stop_loss)? <= benefit
+                    && new_stop_loss_price > order_price
+                {
+                    stop_loss_price = new_stop_loss_price;
+                    order_to_cancel = Some(order);
+                    send_order = true;
+                }
+            } else {
+                let next_stop_loss = match volatility {
+                    Some((_offset, next_stop_loss)) => next_stop_loss,
+                    None => Decimal::from_str(&percentage_to_stop_loss.next_stop_loss)?,
+                };
+
+                if next_stop_loss <= benefit {
+                    stop_loss_price = asset_pair.round_price(
+                        self.calc_stop_loss_price(current_assest.price, stop_loss_offset),
+                    );
+                    order_to_cancel = Some(order.clone());
+                    send_order = true;
+                }
+
+                // A stop below the break even price is raised to it once the gain is reached
+                if break_even_reached
+                    && order_price < break_even_price
+                    && stop_loss_price < break_even_price
+                {
+                    stop_loss_price = break_even_price;
+                    break_even_stop = true;
+                    order_to_cancel = Some(order);
+                    send_order = true;
+                }
+            }
+        } else if Decimal::from_str(&percentage_to_stop_loss.new_stop_loss)? <= benefit {
+            stop_loss_price = asset_pair
+                .round_price(self.calc_stop_loss_price(current_assest.price, stop_loss_offset));
+            send_order = true;
+        } else if break_even_reached {
+            stop_loss_price = break_even_price;
+            break_even_stop = true;
+            send_order = true;
+        }
+
+        if send_order {
+            let order_type = percentage_to_stop_loss.get_order_type()?;
+            let limit_price = match order_type {
+                OrderType::StopLossLimit => {
+                    let limit_offset =
+                        percentage_to_stop_loss.get_limit_offset(self.limit_offset)?;
+
+                    Some(
+                        asset_pair
+                            .round_price(self.calc_stop_loss_price(stop_loss_price, limit_offset))
+                            .to_string(),
+                    )
+                }
+                _ => None,
+            };
+            let volume = asset_pair.round_volume(buy_price.quantity);
+
+            if volume.is_zero() || volume < asset_pair.get_ordermin() {
+                logger::warn("Skipping stop loss below the minimum order size")
+                    .pair(&current_assest.pair)
+                    .field("volume", volume)
+                    .field("ordermin", asset_pair.get_ordermin())
+                    .emit();
+                return Ok((Action::Skipped, None));
+            }
+
+            let stop_loss = OrderRequest {
+                pair: current_assest.pair.clone(),
+                operation_type: get_operation_type(OperationType::SELL),
+                order_type: get_order_type(order_type),
+                price: stop_loss_price.to_string(),
+                price2: limit_price,
+                volume: volume.to_string(),
+                userref: self.userref,
+            };
+
+            let action = if let Some(order) = order_to_cancel {
+                self.replace_order(&order, stop_loss)?;
+
+                Action::Moved
+            } else {
+                self.exchange.add_order(stop_loss)?;
+
+                Action::Placed
+            };
+
+            if break_even_stop {
+                break_evens.insert(buy_price.pair.clone(), buy_price.operation_time);
+            } else {
+                break_evens.remove(&buy_price.pair);
+            }
+
+            self.dancespiele_db.save_break_evens(&break_evens)?;
+
+            return Ok((action, Some(stop_loss_price)));
+        }
+
+        Ok((Action::None, None))
+    }
+
+    /// Entry prices of the current positions, kept to calculate the P&L when their stop executes
+    fn save_positions(&mut self, buy_prices: &[FutureOperation]) -> Result<()> {
+        let mut positions = self.dancespiele_db.fetch_positions()?;
+
+        buy_prices.iter().for_each(|buy_price| {
+            positions.insert(buy_price.pair.clone(), buy_price.buy_price);
+        });
+
+        self.dancespiele_db.save_positions(&positions)
+    }
+
+    /// Stop losses of the worker executed since the last check. The position of the pair
+    /// is closed, so its entry price and take profit state are removed
+    fn detect_stop_fills(&mut self) -> Result<Vec<StopFill>> {
+        let now = Utc::now().timestamp();
+        let checked = match self.dancespiele_db.fetch_fills_checked()? {
+            Some(checked) => checked,
+            None => {
+                self.dancespiele_db.save_fills_checked(now)?;
+                return Ok(vec![]);
+            }
+        };
+
+        let mut closed_orders = HashMap::new();
+        let mut offset = 0;
+
+        loop {
+            let closed_page = self.exchange.get_closed_orders(checked, offset)?;
+            let page_size = closed_page.closed.len() as u32;
+
+            closed_orders.extend(closed_page.closed);
+            offset += page_size;
+
+            if page_size == 0 || offset >= closed_page.count {
+                break;
+            }
+        }
+
+        let asset_pairs = self.get_asset_pairs()?;
+        let mut recorded = self.dancespiele_db.fetch_stop_fills()?;
+        let mut positions = self.dancespiele_db.fetch_positions()?;
+        let mut take_profits = self.dancespiele_db.fetch_take_profits()?;
+        let mut break_evens = self.dancespiele_db.fetch_break_evens()?;
+
+        let mut fills: Vec<StopFill> = closed_orders
+            .into_iter()
+            .filter(|(txid, order)| {
+                order.status == "closed"
+                    && self.is_own_order(order)
+                    && is_sell_order(order, vec![OrderType::StopLoss, OrderType::StopLossLimit])
+                    && !recorded.iter().any(|fill| &fill.txid == txid)
+            })
+            .filter_map(|(txid, order)| {
+                let volume = Decimal::from_str(&order.volume_executed)
+                    .ok()
+                    .filter(|volume| !volume.is_zero())?;
+                let price = Decimal::from_str(&order.average_price).ok()?;
+                let fee = Decimal::from_str(&order.fee).unwrap_or(Decimal::ZERO);
+                let pair = find_asset_pair(&asset_pairs, &order.pair)
+                    .map(|asset_pair| asset_pair.altname)
+                    .unwrap_or(order.pair);
+                let entry_price = positions.get(&pair).cloned();
+
+                Some(StopFill {
+                    txid,
+                    pnl: entry_price
+                        .map(|entry_price| ((price - entry_price) * volume - fee).normalize()),
+                    pair,
+                    price,
+                    volume,
+                    fee,
+                    entry_price,
+                    time: order
+                        .close_time
+                        .map(|close_time| close_time as i64)
+                        .unwrap_or(now),
+                })
+            })
+            .collect();
+
+        fills.sort_by_key(|fill| fill.time);
+
+        fills.iter().for_each(|fill| {
+            positions.remove(&fill.pair);
+            take_profits.remove(&fill.pair);
+            break_evens.remove(&fill.pair);
+
+            metrics::inc(&metrics::STOP_LOSSES, &[("action", "executed")]);
+            logger::info("Stop loss executed")
+                .pair(&fill.pair)
+                .order(&fill.txid)
+                .field("price", fill.price)
+                .field("volume", fill.volume)
+                .field("pnl", fill.pnl)
+                .emit();
+        });
+
+        recorded.extend(fills.clone());
+
+        let keep_from = recorded.len().saturating_sub(STOP_FILLS_KEPT);
+
+        self.dancespiele_db
+            .save_stop_fills(&recorded[keep_from..])?;
+        self.dancespiele_db.save_positions(&positions)?;
+        self.dancespiele_db.save_take_profits(&take_profits)?;
+        self.dancespiele_db.save_break_evens(&break_evens)?;
+        self.dancespiele_db.save_fills_checked(now)?;
+
+        Ok(fills)
+    }
+
+    fn send_notification(&mut self, report: &PairReport) {
+        let notify = Notify::from((report, self.exchange.dry_run()));
+
+        self.notify(&report.pair, async move { send_notification(notify).await });
+    }
+
+    fn send_fill_notification(&mut self, fill: &StopFill) {
+        let notify = NotifyFill::from((fill, self.exchange.dry_run()));
+
+        self.notify(
+            &fill.pair,
+            async move { send_fill_notification(notify).await },
+        );
+    }
+
+    fn notify(&self, pair: &str, notification: impl Future<Output = Result<()>> + Send + 'static) {
+        let runtime = Agnostik::tokio();
+
+        let notification_request = runtime.spawn(notification);
+
+        if let Err(err) = agnostik::block_on(notification_request) {
+            metrics::inc(&metrics::NOTIFICATION_FAILURES, &[]);
+            logger::error("Notification could not be sent")
+                .pair(pair)
+                .field("error", err.to_string())
+                .emit();
+        }
+    }
+
+    fn get_pair_reports(
+        &mut self,
+        current_prices: Vec<CurrentPrice>,
+        active_orders: Vec<StopLossActive>,
+        foreign_stop_losses: Vec<(String, Order)>,
+        buy_prices: Vec<FutureOperation>,
+        actions: &HashMap<String, (Action, Option<Decimal>)>,
+        pair_errors: &HashMap<String, Error>,
+    ) -> Vec<PairReport> {
+        current_prices
+            .into_iter()
+            .map(|current_price| {
+                let active_order_opt = find_stop_loss(&active_orders, &current_price.pair);
+                let buy_price = buy_prices
+                    .clone()
+                    .into_iter()
+                    .find(|bp| bp.pair == current_price.pair);
+
+                let benefit = self.calc_benefit(
+                    if let Some(active_order) = active_order_opt.clone() {
+                        active_order.price
+                    } else if let Some(bp) = buy_price.clone() {
+                        bp.buy_price
+                    } else {
+                        Decimal::ZERO
+                    },
+                    current_price.price,
+                );
+
+                let foreign_stop_losses = foreign_stop_losses
+                    .iter()
+                    .filter(|(_key, order)| order.pair == current_price.pair)
+                    .map(|(key, _order)| key.clone())
+                    .collect();
+                let error = pair_errors
+                    .get(&current_price.pair)
+                    .map(|err| err.to_string());
+                let (action, new_stop_loss) = actions
+                    .get(&current_price.pair)
+                    .cloned()
+                    .unwrap_or((Action::None, None));
+
+                PairReport {
+                    new_stop_loss,
+                    action,
+                    foreign_stop_losses,
+                    error,
+                    ..PairReport::from((
+                        current_price,
+                        if let Some(bp) = buy_price {
+                            bp.buy_price
+                        } else {
+                            Decimal::ZERO
+                        },
+                        benefit,
+                        active_order_opt.map(|sl| sl.price),
+                    ))
+                }
+            })
+            .collect::<Vec<PairReport>>()
+    }
+
+    fn get_current_price(&mut self, pair: &str) -> Result<Decimal> {
+        let (source, volatility) = match self.get_percentage(pair) {
+            Ok(percentage) => (
+                percentage.get_price_source()?,
+                percentage.get_atr_multiplier()?.is_some(),
+            ),
+            Err(_) => (PriceSource::default(), false),
+        };
+
+        let interval = match source {
+            PriceSource::Close(interval) | PriceSource::Vwap(interval) if volatility => interval,
+            _ => return Ok(Decimal::from_str(&self.exchange.get_price(pair, source)?)?),
+        };
+
+        // Candle prices are read from the candles the volatility of the pair needs anyway
+        let candles = self.exchange.get_ohlc(pair, interval)?;
+        let candle = candles
+            .last()
+            .cloned()
+            .ok_or_else(|| Error::Parse(format!("{} has no candles", pair)))?;
+
+        self.candles.insert(pair.to_string(), candles);
+
+        Ok(match source {
+            PriceSource::Vwap(_) => candle.vwap,
+            _ => candle.close,
+        })
+    }
+
+    /// Runs a tick over every configured pair. Failures reading the account abort the tick,
+    /// failures of a pair are reported in its `PairReport` without stopping the rest of pairs
+    pub fn brain(&mut self) -> Result<TickReport> {
+        let tick_id = Utc::now().timestamp_millis().to_string();
+
+        logger::set_context(&tick_id, &self.exchange.name());
+
+        self.percentages = self.dancespiele_db.fetch_coins_percentages_stop_loss()?;
+        self.candles.clear();
+
+        // Fill detection only reports, a failure must not leave the stops of the tick unmanaged
+        let fills = self.detect_stop_fills().unwrap_or_else(|err| {
+            metrics::record_error(&err);
+            logger::error("Stop loss fills could not be detected")
+                .field("error", err.to_string())
+                .emit();
+            vec![]
+        });
+        let (buy_prices, trade_errors) = self.get_buy_prices()?;
+        let mut active_orders = self.exchange.get_active_orders()?.open;
+
+        self.percentages = self.canonical_percentages()?;
+
+        let percentages = self.percentages.clone();
+        let buy_prices: Vec<FutureOperation> = buy_prices
+            .into_iter()
+            .filter(|bp| percentages.iter().any(|p| bp.pair == p.pair))
+            .collect();
+
+        self.save_positions(&buy_prices)?;
+        let mut pair_errors: HashMap<String, Error> = HashMap::new();
+
+        // Pairs without a reliable cost basis are reported and their stops left as they are
+        trade_errors
+            .into_iter()
+            .filter(|(pair, _err)| percentages.iter().any(|p| pair == &p.pair))
+            .for_each(|(pair, err)| {
+                metrics::record_error(&err);
+                logger::error("Cost basis could not be calculated")
+                    .pair(&pair)
+                    .field("error", err.to_string())
+                    .emit();
+                pair_errors.insert(pair, err);
+            });
+
+        let trade_error_pairs: Vec<String> = pair_errors.keys().cloned().collect();
+        let mut actions: HashMap<String, (Action, Option<Decimal>)> = HashMap::new();
+        let mut stop_losses_reconciled = false;
+
+        self.free_volume(&buy_prices, &active_orders)
+            .iter()
+            .for_each(
+                |buy_price| match self.reconcile_stop_losses(buy_price, &active_orders) {
+                    Ok(reconciled) => stop_losses_reconciled |= reconciled,
+                    Err(err) => {
+                        metrics::record_error(&err);
+                        logger::error("Stop losses could not be reconciled")
+                            .pair(&buy_price.pair)
+                            .field("error", err.to_string())
+                            .emit();
+                        pair_errors.insert(buy_price.pair.clone(), err);
+                    }
+                },
+            );
+
+        if stop_losses_reconciled {
+            active_orders = self.exchange.get_active_orders()?.open;
+        }
+
+        let mut take_profits_placed = false;
+
+        buy_prices
+            .iter()
+            .filter(|buy_price| !pair_errors.contains_key(&buy_price.pair))
+            .collect::<Vec<&FutureOperation>>()
+            .into_iter()
+            .for_each(
+                |buy_price| match self.add_take_profit(buy_price, &active_orders) {
+                    Ok(placed) => take_profits_placed |= placed,
+                    Err(err) => {
+                        metrics::record_error(&err);
+                        logger::error("Take profit could not be placed")
+                            .pair(&buy_price.pair)
+                            .field("error", err.to_string())
+                            .emit();
+                        pair_errors.insert(buy_price.pair.clone(), err);
+                    }
+                },
+            );
+
+        if take_profits_placed {
+            active_orders = self.exchange.get_active_orders()?.open;
+        }
+
+        let buy_prices = self.free_volume(&buy_prices, &active_orders);
+
+        let current_prices: Vec<CurrentPrice> = buy_prices
+            .iter()
+            .map(|fo| {
+                let price = self.get_current_price(&fo.pair).unwrap_or_else(|err| {
+                    metrics::record_error(&err);
+                    logger::error("Price could not be fetched")
+                        .pair(&fo.pair)
+                        .field("error", err.to_string())
+                        .emit();
+                    pair_errors.entry(fo.pair.clone()).or_insert(err);
+                    Decimal::ZERO
+                });
+
+                CurrentPrice::from((fo.pair.clone(), price))
+            })
+            .chain(
+                trade_error_pairs
+                    .into_iter()
+                    .map(|pair| CurrentPrice::from((pair, Decimal::ZERO))),
+            )
+            .collect();
+
+        let (stop_losses, foreign_stop_losses): (Vec<_>, Vec<_>) = active_orders
+            .into_iter()
+            .filter(|(_key, order)| {
+                is_sell_order(order, vec![OrderType::StopLoss, OrderType::StopLossLimit])
+                    && current_prices.iter().any(|cp| cp.pair == order.pair)
+            })
+            .partition(|(_key, order)| self.is_own_order(order));
+
+        foreign_stop_losses.iter().for_each(|(key, order)| {
+            logger::info("Stop loss not placed by the worker, leaving it untouched")
+                .pair(&order.pair)
+                .order(key)
+                .emit();
+        });
+
+        let stop_losses: Vec<StopLossActive> = stop_losses
+            .into_iter()
+            .filter_map(|(key, order)| {
+                let current_price = current_prices
+                    .iter()
+                    .find(|cp| cp.pair == order.pair)?
+                    .clone();
+
+                let price = Decimal::from_str(&order.price).unwrap_or_else(|err| {
+                    logger::error("Stop loss price could not be parsed")
+                        .pair(&order.pair)
+                        .order(&key)
+                        .field("error", err.to_string())
+                        .emit();
+                    Decimal::ZERO
+                });
+
+                Some(StopLossActive::from((key, price, current_price)))
+            })
+            .collect();
+        let stop_losses_copy = stop_losses.clone();
+        let buy_prices_copy = buy_prices.clone();
+
+        current_prices
+            .clone()
+            .into_iter()
+            .filter(|cp| !pair_errors.contains_key(&cp.pair))
+            .collect::<Vec<CurrentPrice>>()
+            .into_iter()
+            .for_each(|cp| {
+                let buy_price_opt = buy_prices.clone().into_iter().find(|bp| bp.pair == cp.pair);
+
+                let active_order_opt = find_stop_loss(&stop_losses, &cp.pair);
+
+                if let Some(buy_price) = buy_price_opt {
+                    let pair = cp.pair.clone();
+                    let benefit = self.calc_benefit(
+                        if let Some(active_order) = active_order_opt.clone() {
+                            active_order.price
+                        } else {
+                            buy_price.buy_price
+                        },
+                        cp.price,
+                    );
+
+                    match self.add_stop_loss(
+                        buy_price,
+                        cp,
+                        benefit,
+                        active_order_opt
+                            .map(|active_order| (active_order.order, active_order.price)),
+                    ) {
+                        Ok(action) => {
+                            actions.insert(pair, action);
+                        }
+                        Err(err) => {
+                            metrics::record_error(&err);
+                            logger::error("Stop loss could not be set")
+                                .pair(&pair)
+                                .field("error", err.to_string())
+                                .emit();
+                            pair_errors.insert(pair, err);
+                        }
+                    }
+                } else {
+                    logger::warn("Position not found for the current price")
+                        .pair(&cp.pair)
+                        .emit();
+                }
+            });
+
+        let pair_reports = self.get_pair_reports(
+            current_prices,
+            stop_losses_copy,
+            foreign_stop_losses,
+            buy_prices_copy,
+            &actions,
+            &pair_errors,
+        );
+
+        if self.notify {
+            pair_reports
+                .iter()
+                .filter(|report| report.action == Action::Placed || report.action == Action::Moved)
+                .for_each(|report| self.send_notification(report));
+            fills
+                .iter()
+                .for_each(|fill| self.send_fill_notification(fill));
+        }
+
+        Ok(TickReport {
+            fills,
+            ..TickReport::from((
+                tick_id,
+                Utc::now().timestamp(),
+                self.exchange.dry_run(),
+                pair_reports,
+            ))
+        })
+    }
+}
+
+/// Highest stop loss of the pair, the one the trailing is based on
+fn find_stop_loss(stop_losses: &[StopLossActive], pair: &str) -> Option<StopLossActive> {
+    stop_losses
+        .iter()
+        .filter(|stop_loss| stop_loss.pair == pair)
+        .max_by(|a, b| a.price.cmp(&b.price))
+        .cloned()
+}
+
+fn is_sell_order(order: &Order, order_types: Vec<OrderType>) -> bool {
+    order.operation_type == get_operation_type(OperationType::SELL)
+        && order_types
+            .into_iter()
+            .any(|order_type| order.order_type == get_order_type(order_type))
+}
+
+fn find_asset_pair(asset_pairs: &HashMap<String, AssetPair>, pair: &str) -> Option<AssetPair> {
+    asset_pairs
+        .iter()
+        .find(|(name, asset_pair)| *name == pair || asset_pair.altname == pair)
+        .map(|(_name, asset_pair)| asset_pair.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Worker;
+    use crate::db::{DancespieleDB, Percentage};
+    use crate::dtos::{Action, CurrentPrice, FutureOperation};
+    use crate::exchange::mock::MockExchange;
+    use crate::exchange::types::OrderRequest;
+    use crate::exchange::{Exchange, PaperExchange};
+    use rust_decimal::Decimal;
+
+    fn get_percentages() -> Vec<Percentage> {
+        vec![
+            Percentage {
+                new_stop_loss: String::from("40.0"),
+                next_stop_loss: String::from("14.0"),
+                pair: String::from("KAVAEUR"),
+                ..Percentage::default()
+            },
+            Percentage {
+                new_stop_loss: String::from("30.0"),
+                next_stop_loss: String::from("5.0"),
+                pair: String::from("OXTEUR"),
+                ..Percentage::default()
+            },
+        ]
+    }
+
+    fn get_mock_exchange() -> MockExchange {
+        MockExchange::new()
+            .with_asset_pair("OXTEUR", "OXT", "ZEUR", 5, 8, "10")
+            .with_asset_pair("KAVAEUR", "KAVA", "ZEUR", 4, 8, "1")
+            .with_asset_pair("CRVEUR", "CRV", "ZEUR", 3, 8, "5")
+    }
+
+    fn get_worker(mock_exchange: &mut MockExchange) -> Worker<&mut MockExchange> {
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+
+        Worker::new(mock_exchange, dancespiele_db).without_notifications()
+    }
+
+    fn stop_loss(pair: &str, price: &str, volume: &str) -> OrderRequest {
+        OrderRequest {
+            pair: pair.to_string(),
+            operation_type: String::from("sell"),
+            order_type: String::from("stop-loss"),
+            price: price.to_string(),
+            price2: None,
+            volume: volume.to_string(),
+            userref: None,
+        }
+    }
+
+    #[test]
+    fn should_calculate_benefit() {
+        let mut mock_exchange = get_mock_exchange();
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let benefit = worker.calc_benefit(Decimal::new(29, 2), Decimal::new(4, 1));
+
+        assert_eq!(benefit, Decimal::new(3793, 2));
+        assert_eq!(
+            worker.calc_benefit(Decimal::new(4, 1), Decimal::new(29, 2)),
+            Decimal::ZERO
+        );
+        assert_eq!(
+            worker.calc_benefit(Decimal::ZERO, Decimal::new(4, 1)),
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn should_add_stop_loss() {
+        let mut mock_exchange = get_mock_exchange();
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let buy_price = FutureOperation {
+            break_even_price: Decimal::ZERO,
+            pair: String::from("OXTEUR"),
+            buy_price: Decimal::new(29, 2),
+            operation_time: 160000,
+            quantity: Decimal::new(4000, 0),
+        };
+
+        let current_assest = CurrentPrice {
+            pair: String::from("OXTEUR"),
+            price: Decimal::new(4, 1),
+        };
+
+        worker
+            .add_stop_loss(buy_price, current_assest, Decimal::new(3793, 2), None)
+            .unwrap();
+
+        assert!(mock_exchange.cancelled_orders().is_empty());
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.392", "4000")]
+        );
+    }
+
+    #[test]
+    fn should_use_stop_loss_offset_per_pair() {
+        let mut mock_exchange = get_mock_exchange();
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[
+            Percentage {
+                new_stop_loss: String::from("10.0"),
+                next_stop_loss: String::from("5.0"),
+                pair: String::from("KAVAEUR"),
+                stop_loss_offset: Some(String::from("5.0")),
+                ..Percentage::default()
+            },
+            Percentage {
+                new_stop_loss: String::from("30.0"),
+                next_stop_loss: String::from("5.0"),
+                pair: String::from("OXTEUR"),
+                ..Percentage::default()
+            },
+        ]);
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db)
+            .with_stop_loss_offset(Decimal::new(10, 0))
+            .without_notifications();
+
+        worker
+            .add_stop_loss(
+                FutureOperation {
+                    break_even_price: Decimal::ZERO,
+                    pair: String::from("KAVAEUR"),
+                    buy_price: Decimal::new(3, 0),
+                    operation_time: 160000,
+                    quantity: Decimal::new(1500, 0),
+                },
+                CurrentPrice {
+                    pair: String::from("KAVAEUR"),
+                    price: Decimal::new(35, 1),
+                },
+                Decimal::new(1666, 2),
+                None,
+            )
+            .unwrap();
+        worker
+            .add_stop_loss(
+                FutureOperation {
+                    break_even_price: Decimal::ZERO,
+                    pair: String::from("OXTEUR"),
+                    buy_price: Decimal::new(29, 2),
+                    operation_time: 160000,
+                    quantity: Decimal::new(4000, 0),
+                },
+                CurrentPrice {
+                    pair: String::from("OXTEUR"),
+                    price: Decimal::new(4, 1),
+                },
+                Decimal::new(3793, 2),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                stop_loss("KAVAEUR", "3.325", "1500"),
+                stop_loss("OXTEUR", "0.36", "4000")
+            ]
+        );
+    }
+
+    #[test]
+    fn should_add_stop_loss_limit() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_prices("KAVAEUR", &["3.5", "4.0"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("10.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("KAVAEUR"),
+            order_type: Some(String::from("stop-loss-limit")),
+            limit_offset: Some(String::from("2.0")),
+            ..Percentage::default()
+        }]);
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db).without_notifications();
+
+        worker.brain().unwrap();
+        worker.brain().unwrap();
+
+        let placed_orders = mock_exchange.placed_orders();
+
+        assert_eq!(placed_orders.len(), 2);
+        assert_eq!(placed_orders[0].order_type, "stop-loss-limit");
+        assert_eq!(placed_orders[0].price, "3.43");
+        assert_eq!(placed_orders[0].price2, Some(String::from("3.3614")));
+        assert_eq!(placed_orders[1].price, "3.92");
+        assert_eq!(placed_orders[1].price2, Some(String::from("3.8416")));
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec!["OMOCK-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn should_place_take_profit_once_per_position() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_prices("KAVAEUR", &["3.5"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("10.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("KAVAEUR"),
+            take_profit: Some(String::from("50.0")),
+            ..Percentage::default()
+        }]);
+
+        let mut worker =
+            Worker::new(&mut mock_exchange, dancespiele_db.clone()).without_notifications();
+
+        worker.brain().unwrap();
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                OrderRequest {
+                    order_type: String::from("take-profit"),
+                    ..stop_loss("KAVAEUR", "4.5", "750")
+                },
+                stop_loss("KAVAEUR", "3.43", "750")
+            ]
+        );
+
+        mock_exchange.cancel_order("OMOCK-1").unwrap();
+
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db).without_notifications();
+
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange
+                .placed_orders()
+                .iter()
+                .filter(|order| order.order_type == "take-profit")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn should_place_take_profit_live_after_dry_run() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_prices("KAVAEUR", &["3.5"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("10.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("KAVAEUR"),
+            take_profit: Some(String::from("50.0")),
+            ..Percentage::default()
+        }]);
+
+        Worker::new(
+            PaperExchange::new(&mut mock_exchange, dancespiele_db.clone()),
+            dancespiele_db.clone(),
+        )
+        .without_notifications()
+        .brain()
+        .unwrap();
+
+        assert_eq!(dancespiele_db.fetch_paper_orders().unwrap().len(), 2);
+        assert!(mock_exchange.placed_orders().is_empty());
+
+        Worker::new(&mut mock_exchange, dancespiele_db)
+            .without_notifications()
+            .brain()
+            .unwrap();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                OrderRequest {
+                    order_type: String::from("take-profit"),
+                    ..stop_loss("KAVAEUR", "4.5", "750")
+                },
+                stop_loss("KAVAEUR", "3.43", "750")
+            ]
+        );
+    }
+
+    #[test]
+    fn should_free_stop_loss_volume_for_take_profit() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"))
+            .with_prices("KAVAEUR", &["3.2"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("40.0"),
+            next_stop_loss: String::from("14.0"),
+            pair: String::from("KAVAEUR"),
+            take_profit: Some(String::from("50.0")),
+            take_profit_volume: Some(String::from("40.0")),
+            take_profit_order_type: Some(String::from("take-profit-limit")),
+            ..Percentage::default()
+        }]);
+
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db).without_notifications();
+
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec!["3344de344".to_string()]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                stop_loss("KAVAEUR", "3.0", "900"),
+                OrderRequest {
+                    order_type: String::from("take-profit-limit"),
+                    price2: Some(String::from("4.455")),
+                    ..stop_loss("KAVAEUR", "4.5", "600")
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn should_leave_foreign_stop_losses_untouched() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_balance("OXT", "4000")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_open_order("manual-1", stop_loss("KAVAEUR", "3.0", "1500"))
+            .with_open_order(
+                "OWN-1",
+                OrderRequest {
+                    userref: Some(7),
+                    ..stop_loss("OXTEUR", "0.3", "4000")
+                },
+            )
+            .with_prices("KAVAEUR", &["4.0"])
+            .with_prices("OXTEUR", &["0.4"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db)
+            .with_userref(7)
+            .without_notifications();
+
+        let report = worker.brain().unwrap();
+
+        let kava = report.pairs.iter().find(|r| r.pair == "KAVAEUR").unwrap();
+        let oxt = report.pairs.iter().find(|r| r.pair == "OXTEUR").unwrap();
+
+        assert_eq!(kava.foreign_stop_losses, vec![String::from("manual-1")]);
+        assert_eq!(kava.current_stop_loss, None);
+        assert_eq!(oxt.action, Action::Moved);
+        assert_eq!(mock_exchange.cancelled_orders(), vec!["OWN-1".to_string()]);
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![OrderRequest {
+                userref: Some(7),
+                ..stop_loss("OXTEUR", "0.392", "4000")
+            }]
+        );
+        assert!(mock_exchange.open_orders().contains_key("manual-1"));
+    }
+
+    #[test]
+    fn should_leave_volume_of_foreign_stop_loss_out_of_stop_loss() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_open_order("manual-1", stop_loss("KAVAEUR", "3.0", "500"))
+            .with_open_order(
+                "OWN-1",
+                OrderRequest {
+                    userref: Some(7),
+                    ..stop_loss("KAVAEUR", "3.1", "1500")
+                },
+            )
+            .with_prices("KAVAEUR", &["3.2"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db)
+            .with_userref(7)
+            .without_notifications();
+
+        worker.brain().unwrap();
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("OWN-1")]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![OrderRequest {
+                userref: Some(7),
+                ..stop_loss("KAVAEUR", "3.1", "1000")
+            }]
+        );
+        assert!(mock_exchange.open_orders().contains_key("manual-1"));
+    }
+
+    #[test]
+    fn should_consolidate_stop_losses_of_pair() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1000"))
+            .with_open_order("3344de345", stop_loss("KAVAEUR", "3.1", "800"))
+            .with_executed_volume("3344de345", "300")
+            .with_prices("KAVAEUR", &["3.2"]);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec!["3344de344".to_string(), "3344de345".to_string()]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("KAVAEUR", "3.1", "1500")]
+        );
+        assert_eq!(mock_exchange.open_orders().len(), 1);
+    }
+
+    #[test]
+    fn should_resize_stop_loss_to_balance() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1000"))
+            .with_prices("KAVAEUR", &["3.2"]);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        worker.brain().unwrap();
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec!["3344de344".to_string()]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("KAVAEUR", "3.0", "1500")]
+        );
+    }
+
+    #[test]
+    fn should_keep_running_other_pairs_when_one_fails() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_balance("OXT", "4000")
+            .with_trade("KAVAEUR", "buy", "3.0", "1500", 160000.0)
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_prices("OXTEUR", &["0.4"]);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let report = worker.brain().unwrap();
+
+        let kava = report.pairs.iter().find(|r| r.pair == "KAVAEUR").unwrap();
+        let oxt = report.pairs.iter().find(|r| r.pair == "OXTEUR").unwrap();
+        let report_json: serde_json::Value = serde_json::to_value(&report).unwrap();
+
+        assert!(kava.error.as_ref().unwrap().starts_with("exchange error"));
+        assert_eq!(kava.action, Action::None);
+        assert_eq!(oxt.error, None);
+        assert_eq!(oxt.action, Action::Placed);
+        assert_eq!(oxt.reference_price, Decimal::new(29, 2));
+        assert_eq!(oxt.new_stop_loss, Some(Decimal::new(392, 3)));
+        assert!(report_json["pairs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|pair| pair["action"] == "placed" && pair["new_stop_loss"] == "0.392"));
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.392", "4000")]
+        );
+    }
+
+    #[test]
+    fn should_set_next_stop_loss() {
+        let mut mock_exchange =
+            get_mock_exchange().with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"));
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let buy_price = FutureOperation {
+            break_even_price: Decimal::ZERO,
+            pair: String::from("KAVAEUR"),
+            buy_price: Decimal::new(3, 0),
+            operation_time: 160000,
+            quantity: Decimal::new(1500, 0),
+        };
+
+        let current_assest = CurrentPrice {
+            pair: String::from("KAVAEUR"),
+            price: Decimal::new(35, 1),
+        };
+
+        worker
+            .add_stop_loss(
+                buy_price,
+                current_assest,
+                Decimal::new(1666, 2),
+                Some((String::from("3344de344"), Decimal::new(3, 0))),
+            )
+            .unwrap();
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("3344de344")]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("KAVAEUR", "3.43", "1500")]
+        );
+    }
+
+    #[test]
+    fn should_edit_stop_loss_in_place() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_order_editing()
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"));
+        let mut worker = get_worker(&mut mock_exchange);
+
+        worker
+            .add_stop_loss(
+                FutureOperation {
+                    break_even_price: Decimal::ZERO,
+                    pair: String::from("KAVAEUR"),
+                    buy_price: Decimal::new(3, 0),
+                    operation_time: 160000,
+                    quantity: Decimal::new(1500, 0),
+                },
+                CurrentPrice {
+                    pair: String::from("KAVAEUR"),
+                    price: Decimal::new(35, 1),
+                },
+                Decimal::new(1666, 2),
+                Some((String::from("3344de344"), Decimal::new(3, 0))),
+            )
+            .unwrap();
+
+        let replacements = worker.dancespiele_db.fetch_stop_replacements().unwrap();
+
+        assert_eq!(replacements[0].outcome, "edited");
+        assert!(mock_exchange.cancelled_orders().is_empty());
+        assert!(mock_exchange.placed_orders().is_empty());
+        assert_eq!(
+            mock_exchange.edited_orders(),
+            vec![(
+                String::from("3344de344"),
+                stop_loss("KAVAEUR", "3.43", "1500")
+            )]
+        );
+    }
+
+    #[test]
+    fn should_record_failed_edit_of_stop_loss() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_order_editing()
+            .with_rejected_price("3.43")
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"));
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let result = worker.add_stop_loss(
+            FutureOperation {
+                break_even_price: Decimal::ZERO,
+                pair: String::from("KAVAEUR"),
+                buy_price: Decimal::new(3, 0),
+                operation_time: 160000,
+                quantity: Decimal::new(1500, 0),
+            },
+            CurrentPrice {
+                pair: String::from("KAVAEUR"),
+                price: Decimal::new(35, 1),
+            },
+            Decimal::new(1666, 2),
+            Some((String::from("3344de344"), Decimal::new(3, 0))),
+        );
+
+        let replacements = worker.dancespiele_db.fetch_stop_replacements().unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(replacements[0].outcome, "edit_failed");
+        assert!(replacements[0].error.is_some());
+        assert!(mock_exchange.cancelled_orders().is_empty());
+    }
+
+    #[test]
+    fn should_restore_previous_stop_loss_when_new_one_fails() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_rejected_price("3.43")
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"));
+        let mut worker = get_worker(&mut mock_exchange);
+
+        worker
+            .add_stop_loss(
+                FutureOperation {
+                    break_even_price: Decimal::ZERO,
+                    pair: String::from("KAVAEUR"),
+                    buy_price: Decimal::new(3, 0),
+                    operation_time: 160000,
+                    quantity: Decimal::new(1500, 0),
+                },
+                CurrentPrice {
+                    pair: String::from("KAVAEUR"),
+                    price: Decimal::new(35, 1),
+                },
+                Decimal::new(1666, 2),
+                Some((String::from("3344de344"), Decimal::new(3, 0))),
+            )
+            .unwrap_err();
+
+        let replacements = worker.dancespiele_db.fetch_stop_replacements().unwrap();
+
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(replacements[0].txid, "3344de344");
+        assert_eq!(replacements[0].price, "3.43");
+        assert_eq!(replacements[0].outcome, "restored");
+        assert!(replacements[0].error.is_some());
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("KAVAEUR", "3.0", "1500")]
+        );
+        assert_eq!(mock_exchange.open_orders().len(), 1);
+    }
+
+    #[test]
+    fn should_not_move_stop_loss_below_next_percentage() {
+        let mut mock_exchange =
+            get_mock_exchange().with_open_order("3344de344", stop_loss("KAVAEUR", "3.2", "1500"));
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let buy_price = FutureOperation {
+            break_even_price: Decimal::ZERO,
+            pair: String::from("KAVAEUR"),
+            buy_price: Decimal::new(3, 0),
+            operation_time: 160000,
+            quantity: Decimal::new(1500, 0),
+        };
+
+        let current_assest = CurrentPrice {
+            pair: String::from("KAVAEUR"),
+            price: Decimal::new(35, 1),
+        };
+
+        worker
+            .add_stop_loss(
+                buy_price,
+                current_assest,
+                Decimal::new(937, 2),
+                Some((String::from("3344de344"), Decimal::new(32, 1))),
+            )
+            .unwrap();
+
+        assert!(mock_exchange.cancelled_orders().is_empty());
+        assert!(mock_exchange.placed_orders().is_empty());
+    }
+
+    #[test]
+    fn should_round_stop_loss_to_pair_precision() {
+        let mut mock_exchange =
+            MockExchange::new().with_asset_pair("KAVAEUR", "KAVA", "ZEUR", 2, 4, "1");
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let buy_price = FutureOperation {
+            break_even_price: Decimal::ZERO,
+            pair: String::from("KAVAEUR"),
+            buy_price: Decimal::new(2, 0),
+            operation_time: 160000,
+            quantity: Decimal::new(1500123456789, 9),
+        };
+
+        let current_assest = CurrentPrice {
+            pair: String::from("KAVAEUR"),
+            price: Decimal::new(3456, 3),
+        };
+
+        worker
+            .add_stop_loss(buy_price, current_assest, Decimal::new(7280, 2), None)
+            .unwrap();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("KAVAEUR", "3.38", "1500.1234")]
+        );
+    }
+
+    #[test]
+    fn should_skip_stop_loss_below_minimum_order() {
+        let mut mock_exchange =
+            get_mock_exchange().with_open_order("3344de344", stop_loss("OXTEUR", "0.3", "5"));
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let buy_price = FutureOperation {
+            break_even_price: Decimal::ZERO,
+            pair: String::from("OXTEUR"),
+            buy_price: Decimal::new(29, 2),
+            operation_time: 160000,
+            quantity: Decimal::new(5, 0),
+        };
+
+        let current_assest = CurrentPrice {
+            pair: String::from("OXTEUR"),
+            price: Decimal::new(4, 1),
+        };
+
+        worker
+            .add_stop_loss(
+                buy_price,
+                current_assest,
+                Decimal::new(3333, 2),
+                Some((String::from("3344de344"), Decimal::new(3, 1))),
+            )
+            .unwrap();
+
+        assert!(mock_exchange.placed_orders().is_empty());
+        assert!(mock_exchange.cancelled_orders().is_empty());
+    }
+
+    #[test]
+    fn should_resolve_assets_from_asset_pairs() {
+        let mut mock_exchange = MockExchange::new()
+            .with_asset_pair("XXBTZEUR", "XXBT", "ZEUR", 1, 8, "0.0001")
+            .with_asset_pair("XREPZEUR", "XREP", "ZEUR", 3, 8, "0.3")
+            .with_asset_pair("REPV2EUR", "REPV2", "ZEUR", 3, 8, "0.3")
+            .with_balance("XXBT", "0.5")
+            .with_balance("REPV2", "10")
+            .with_trade("XXBTZEUR", "buy", "10000.0", "0.5", 160000.0)
+            .with_trade("XREPZEUR", "buy", "10.0", "10", 160000.0)
+            .with_trade("REPV2EUR", "buy", "10.0", "10", 160000.0);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let (mut buy_prices, _trade_errors) = worker.get_buy_prices().unwrap();
+        buy_prices.sort_by(|a, b| a.pair.cmp(&b.pair));
+
+        assert_eq!(buy_prices.len(), 2);
+        assert_eq!(buy_prices[0].pair, "REPV2EUR");
+        assert_eq!(buy_prices[0].quantity, Decimal::new(10, 0));
+        assert_eq!(buy_prices[1].pair, "XBTEUR");
+        assert_eq!(buy_prices[1].quantity, Decimal::new(5, 1));
+    }
+
+    #[test]
+    fn should_use_weighted_average_as_buy_price() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "150")
+            .with_trade("KAVAEUR", "buy", "2.0", "100", 160000.0)
+            .with_trade("KAVAEUR", "buy", "4.0", "100", 160100.0)
+            .with_trade("KAVAEUR", "sell", "4.5", "50", 160200.0);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let (buy_prices, _trade_errors) = worker.get_buy_prices().unwrap();
+
+        assert_eq!(buy_prices.len(), 1);
+        assert_eq!(buy_prices[0].buy_price, Decimal::new(3, 0));
+        assert_eq!(buy_prices[0].quantity, Decimal::new(150, 0));
+        assert_eq!(buy_prices[0].operation_time, 160100);
+    }
+
+    #[test]
+    fn should_report_pair_with_trade_that_can_not_be_parsed() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("KAVA", "1500")
+            .with_balance("OXT", "4000")
+            .with_trade("KAVAEUR", "buy", "3.0", "1,500", 160000.0)
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "3.0", "1500"))
+            .with_prices("KAVAEUR", &["4.5"])
+            .with_prices("OXTEUR", &["0.4"]);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        let report = worker.brain().unwrap();
+
+        let kava = report.pairs.iter().find(|r| r.pair == "KAVAEUR").unwrap();
+        let oxt = report.pairs.iter().find(|r| r.pair == "OXTEUR").unwrap();
+
+        assert!(kava.error.as_ref().unwrap().starts_with("parse error"));
+        assert_eq!(kava.action, Action::None);
+        assert_eq!(kava.current_stop_loss, Some(Decimal::new(3, 0)));
+        assert_eq!(oxt.action, Action::Placed);
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.392", "4000")]
+        );
+        assert!(mock_exchange.cancelled_orders().is_empty());
+    }
+
+    #[test]
+    fn should_sync_trades_incrementally() {
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+
+        let mut mock_exchange = get_mock_exchange()
+            .with_page_size(2)
+            .with_trade("KAVAEUR", "buy", "2.0", "100", 160000.0)
+            .with_trade("KAVAEUR", "buy", "2.1", "100", 160100.0)
+            .with_trade("KAVAEUR", "buy", "2.2", "100", 160200.0)
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160300.0)
+            .with_trade("OXTEUR", "buy", "0.30", "4000", 160400.0);
+
+        let trades = Worker::new(&mut mock_exchange, dancespiele_db.clone())
+            .sync_trades()
+            .unwrap();
+
+        assert_eq!(trades.len(), 5);
+        assert_eq!(mock_exchange.trades_offsets(), vec![0, 2, 4]);
+
+        mock_exchange = mock_exchange.with_trade("KAVAEUR", "sell", "3.0", "50", 160500.0);
+
+        let trades = Worker::new(&mut mock_exchange, dancespiele_db.clone())
+            .sync_trades()
+            .unwrap();
+
+        assert_eq!(trades.len(), 6);
+        assert!(trades.contains_key("TMOCK-6"));
+        assert_eq!(mock_exchange.trades_offsets(), vec![0, 2, 4, 0]);
+        assert_eq!(dancespiele_db.fetch_trades().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn should_resume_trades_backfill_from_failed_page() {
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+
+        let trades_exchange = || {
+            get_mock_exchange()
+                .with_page_size(2)
+                .with_trade("KAVAEUR", "buy", "2.0", "100", 160000.0)
+                .with_trade("KAVAEUR", "buy", "2.1", "100", 160100.0)
+                .with_trade("KAVAEUR", "buy", "2.2", "100", 160200.0)
+                .with_trade("OXTEUR", "buy", "0.29", "4000", 160300.0)
+                .with_trade("OXTEUR", "buy", "0.30", "4000", 160400.0)
+        };
+
+        let mut mock_exchange = trades_exchange().with_trades_failing_from(4);
+
+        assert!(Worker::new(&mut mock_exchange, dancespiele_db.clone())
+            .sync_trades()
+            .is_err());
+        assert_eq!(dancespiele_db.fetch_trades().unwrap().len(), 4);
+
+        let mut mock_exchange = trades_exchange();
+
+        let trades = Worker::new(&mut mock_exchange, dancespiele_db.clone())
+            .sync_trades()
+            .unwrap();
+
+        assert_eq!(trades.len(), 5);
+        assert_eq!(mock_exchange.trades_offsets(), vec![4]);
+        assert!(dancespiele_db.fetch_trades_sync().unwrap().complete);
+    }
+
+    #[test]
+    fn should_brain_play_in_kraken() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_balance("KAVA", "1500")
+            .with_balance("CRV", "3000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_trade("KAVAEUR", "buy", "2.0", "1500", 160000.0)
+            .with_trade("CRVEUR", "buy", "5.0", "3000", 160000.0)
+            .with_open_order("3344de344", stop_loss("KAVAEUR", "0.3", "1500"))
+            .with_prices("OXTEUR", &["0.40"])
+            .with_prices("KAVAEUR", &["3.5"])
+            .with_prices("CRVEUR", &["0.40"]);
+
+        get_worker(&mut mock_exchange).brain().unwrap();
+
+        let mut placed_orders = mock_exchange.placed_orders();
+        placed_orders.sort_by(|a, b| a.pair.cmp(&b.pair));
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("3344de344")]
+        );
+        assert_eq!(
+            placed_orders,
+            vec![
+                stop_loss("KAVAEUR", "3.43", "1500"),
+                stop_loss("OXTEUR", "0.392", "4000"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_trail_stop_loss_over_several_ticks() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_prices("OXTEUR", &["0.30", "0.40", "0.41", "0.45"]);
+        let mut worker = get_worker(&mut mock_exchange);
+
+        for _ in 0..4 {
+            worker.brain().unwrap();
+        }
+
+        let open_orders = mock_exchange.open_orders();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                stop_loss("OXTEUR", "0.392", "4000"),
+                stop_loss("OXTEUR", "0.441", "4000"),
+            ]
+        );
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("OMOCK-1")]
+        );
+        assert_eq!(open_orders.len(), 1);
+        assert_eq!(open_orders.get("OMOCK-2").unwrap().price, "0.441");
+    }
+
+    #[test]
+    fn should_trail_open_stop_loss_from_its_trigger_price() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_open_order("3344de344", stop_loss("OXTEUR", "0.38", "4000"))
+            .with_prices("OXTEUR", &["0.40"]);
+
+        get_worker(&mut mock_exchange).brain().unwrap();
+
+        let open_orders = mock_exchange.open_orders();
+        let trailed = open_orders.get("OMOCK-1").unwrap();
+
+        assert_eq!(trailed.price, "0.392");
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("3344de344")]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.392", "4000")]
+        );
+    }
+
+    #[test]
+    fn should_trail_stop_loss_off_configured_price_source() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_prices("OXTEUR", &["0.30"])
+            .with_ticker("OXTEUR", "0.40", "0.42", "0.41");
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("30.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("OXTEUR"),
+            price_source: Some(String::from("bid")),
+            ..Percentage::default()
+        }]);
+
+        Worker::new(&mut mock_exchange, dancespiele_db)
+            .without_notifications()
+            .brain()
+            .unwrap();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.392", "4000")]
+        );
+    }
+
+    #[test]
+    fn should_trail_stop_loss_by_average_true_range() {
+        let candles = [
+            ("0.37", "0.36", "0.37"),
+            ("0.38", "0.37", "0.38"),
+            ("0.39", "0.38", "0.39"),
+            ("0.40", "0.39", "0.40"),
+            ("0.405", "0.395", "0.405"),
+            ("0.42", "0.41", "0.42"),
+        ];
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("30.0"),
+            next_stop_loss: String::from("1.0"),
+            pair: String::from("OXTEUR"),
+            atr_multiplier: Some(String::from("2")),
+            atr_period: Some(String::from("3")),
+            ..Percentage::default()
+        }]);
+
+        for tick in 4..=candles.len() {
+            mock_exchange = mock_exchange.with_candles("OXTEUR", &candles[..tick]);
+
+            Worker::new(&mut mock_exchange, dancespiele_db.clone())
+                .without_notifications()
+                .brain()
+                .unwrap();
+        }
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                stop_loss("OXTEUR", "0.38", "4000"),
+                stop_loss("OXTEUR", "0.39666", "4000"),
+            ]
+        );
+        assert_eq!(mock_exchange.ohlc_requests(), 3);
+    }
+
+    #[test]
+    fn should_use_fixed_offset_when_average_true_range_exceeds_price() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_candles(
+                "OXTEUR",
+                &[
+                    ("0.37", "0.36", "0.37"),
+                    ("0.38", "0.37", "0.38"),
+                    ("0.39", "0.38", "0.39"),
+                    ("0.42", "0.41", "0.42"),
+                ],
+            );
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("30.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("OXTEUR"),
+            atr_multiplier: Some(String::from("100")),
+            atr_period: Some(String::from("3")),
+            ..Percentage::default()
+        }]);
+
+        Worker::new(&mut mock_exchange, dancespiele_db)
+            .without_notifications()
+            .brain()
+            .unwrap();
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.4116", "4000")]
+        );
+    }
+
+    #[test]
+    fn should_place_break_even_stop_before_stop_loss_ladder() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_prices("OXTEUR", &["0.295", "0.31", "0.32", "0.40"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("30.0"),
+            next_stop_loss: String::from("5.0"),
+            pair: String::from("OXTEUR"),
+            break_even: Some(String::from("5.0")),
+            ..Percentage::default()
+        }]);
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db).without_notifications();
+
+        for _ in 0..4 {
+            worker.brain().unwrap();
+        }
+
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![
+                stop_loss("OXTEUR", "0.29", "4000"),
+                stop_loss("OXTEUR", "0.392", "4000"),
+            ]
+        );
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("OMOCK-1")]
+        );
+    }
+
+    #[test]
+    fn should_raise_ladder_stop_below_break_even_price() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.291234", "4000", 160000.0)
+            .with_open_order("3344de344", stop_loss("OXTEUR", "0.28", "4000"))
+            .with_prices("OXTEUR", &["0.32"]);
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&[Percentage {
+            new_stop_loss: String::from("30.0"),
+            next_stop_loss: String::from("50.0"),
+            pair: String::from("OXTEUR"),
+            break_even: Some(String::from("5.0")),
+            ..Percentage::default()
+        }]);
+
+        let mut worker = Worker::new(&mut mock_exchange, dancespiele_db).without_notifications();
+
+        worker.brain().unwrap();
+        worker.brain().unwrap();
+
+        assert_eq!(
+            mock_exchange.cancelled_orders(),
+            vec![String::from("3344de344")]
+        );
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.29124", "4000")]
+        );
+    }
+
+    #[test]
+    fn should_record_executed_stop_loss_and_clean_pair_state() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_closed_order(
+                "OSTOP-1",
+                stop_loss("KAVAEUR", "3.43", "1500"),
+                "3.42",
+                1600000100.0,
+            )
+            .with_closed_order(
+                "OSTOP-0",
+                stop_loss("KAVAEUR", "2.9", "1500"),
+                "2.9",
+                1590000000.0,
+            );
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+        dancespiele_db
+            .save_positions(
+                &vec![(String::from("KAVAEUR"), Decimal::new(3, 0))]
+                    .into_iter()
+                    .collect(),
+            )
+            .unwrap();
+        dancespiele_db
+            .save_take_profits(
+                &vec![(String::from("KAVAEUR"), 160000)]
+                    .into_iter()
+                    .collect(),
+            )
+            .unwrap();
+        dancespiele_db.save_fills_checked(1600000000).unwrap();
+
+        let mut worker =
+            Worker::new(&mut mock_exchange, dancespiele_db.clone()).without_notifications();
+
+        let report = worker.brain().unwrap();
+        let second_report = worker.brain().unwrap();
+
+        assert_eq!(report.fills.len(), 1);
+        assert_eq!(report.fills[0].txid, "OSTOP-1");
+        assert_eq!(report.fills[0].pnl, Some(Decimal::new(630, 0)));
+        assert!(second_report.fills.is_empty());
+        assert!(dancespiele_db.fetch_positions().unwrap().is_empty());
+        assert!(dancespiele_db.fetch_take_profits().unwrap().is_empty());
+        assert_eq!(dancespiele_db.fetch_stop_fills().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn should_manage_stop_losses_when_closed_orders_fail() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_balance("OXT", "4000")
+            .with_trade("OXTEUR", "buy", "0.29", "4000", 160000.0)
+            .with_prices("OXTEUR", &["0.40"])
+            .with_closed_orders_failing();
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+        dancespiele_db.save_fills_checked(1600000000).unwrap();
+
+        let report = Worker::new(&mut mock_exchange, dancespiele_db.clone())
+            .without_notifications()
+            .brain()
+            .unwrap();
+
+        assert!(report.fills.is_empty());
+        assert_eq!(
+            mock_exchange.placed_orders(),
+            vec![stop_loss("OXTEUR", "0.392", "4000")]
+        );
+        assert_eq!(
+            dancespiele_db.fetch_fills_checked().unwrap(),
+            Some(1600000000)
+        );
+    }
+
+    #[test]
+    fn should_read_every_page_of_closed_orders() {
+        let mut mock_exchange = get_mock_exchange()
+            .with_page_size(1)
+            .with_closed_order(
+                "OSTOP-1",
+                stop_loss("KAVAEUR", "3.43", "1500"),
+                "3.42",
+                1600000100.0,
+            )
+            .with_closed_order(
+                "OSTOP-2",
+                stop_loss("OXTEUR", "0.392", "4000"),
+                "0.39",
+                1600000200.0,
+            );
+        let mut dancespiele_db = DancespieleDB::temporary();
+        dancespiele_db.save_coins_percentages_stop_loss(&get_percentages());
+        dancespiele_db.save_fills_checked(1600000000).unwrap();
+
+        let report = Worker::new(&mut mock_exchange, dancespiele_db)
+            .without_notifications()
+            .brain()
+            .unwrap();
+
+        assert_eq!(
+            report
+                .fills
+                .iter()
+                .map(|fill| fill.txid.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["OSTOP-1", "OSTOP-2"]
+        );
+    }
+}